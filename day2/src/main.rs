@@ -2,12 +2,14 @@
 
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_while1},
-    combinator::{eof, map_res},
-    sequence::{separated_pair, terminated},
+    bytes::complete::tag,
+    character::complete::{char, digit1, space0, space1},
+    combinator::{eof, map, map_res, opt, recognize},
+    sequence::{delimited, pair, separated_pair, terminated, tuple},
     IResult,
 };
 use std::env;
+use std::fmt::{self, Display, Formatter};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
@@ -17,93 +19,294 @@ enum Part {
 }
 
 /// The direction of a sub command
+#[derive(Clone)]
 enum Direction {
     Forward(i32),
+    Backward(i32),
     Down(i32),
     Up(i32),
 }
 
+impl Display for Direction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Forward(n) => write!(f, "forward {n}"),
+            Direction::Backward(n) => write!(f, "backward {n}"),
+            Direction::Down(n) => write!(f, "down {n}"),
+            Direction::Up(n) => write!(f, "up {n}"),
+        }
+    }
+}
+
+/// A single statement of a command script
+enum Statement {
+    Move(Direction),
+    /// Opens a block whose contents are repeated the given number of times
+    RepeatStart(u32),
+    /// Closes the innermost repeat block
+    RepeatEnd,
+}
+
+/// A parsed command of a script, along with the (1-indexed) line it was written on. Repeat blocks are kept as they are
+/// written, rather than expanded, so that scripts with large repeat counts stay small.
+enum Command {
+    Move {
+        direction: Direction,
+        line_number: usize,
+    },
+    Repeat {
+        count: u32,
+        body: Vec<Command>,
+        line_number: usize,
+    },
+}
+
+/// A repeat block that has been opened, but not yet closed
+struct OpenBlock {
+    count: u32,
+    opened_on: usize,
+    body: Vec<Command>,
+}
+
+#[derive(Debug)]
+enum ParseErrorKind {
+    InvalidStatement(String),
+    UnmatchedBlockEnd,
+    UnclosedBlock,
+}
+
+/// An error encountered while parsing a command script, along with the (1-indexed) line it occurred on
+#[derive(Debug)]
+struct ParseError {
+    line_number: usize,
+    kind: ParseErrorKind,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line_number)?;
+        match &self.kind {
+            ParseErrorKind::InvalidStatement(err) => write!(f, "invalid statement ({err})"),
+            ParseErrorKind::UnmatchedBlockEnd => write!(f, "'}}' does not close any repeat block"),
+            ParseErrorKind::UnclosedBlock => write!(f, "repeat block is never closed"),
+        }
+    }
+}
+
+/// An error encountered while moving the sub: the command on the given (1-indexed) line takes it further than a
+/// location can represent
+#[derive(Debug)]
+struct OverflowError {
+    line_number: usize,
+}
+
+impl Display for OverflowError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: the sub's location overflows", self.line_number)
+    }
+}
+
 /// The location of the sub
-#[derive(Default)]
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
 struct Location {
-    position: i32,
-    depth: i32,
-    aim: i32,
+    position: i64,
+    depth: i64,
+    aim: i64,
 }
 
 impl Location {
-    /// Produce a new location that is moved based  based on simple directionality:
-    /// forward/up/down map to positions and depths
-    fn perform_directional_move(&self, direction: &Direction) -> Location {
-        match direction {
-            Direction::Up(n) => Location {
-                position: self.position,
-                depth: self.depth - n,
-                aim: self.aim,
-            },
-            Direction::Down(n) => Location {
-                position: self.position,
-                depth: self.depth + n,
-                aim: self.aim,
+    /// Produce the location reached by performing a single move from the origin.
+    ///
+    /// In part 1, moves are based on simple directionality: forward/up/down map to positions and depths. In part 2,
+    /// up/down change the aim, and depth is controlled by a multiple of aim when moving forward (which is handled when
+    /// moves are combined with `then`, as it depends on the aim before the move).
+    fn of_move(direction: &Direction, part: &Part) -> Location {
+        // Magnitudes are i32, so negating them can't overflow once widened
+        let (position, vertical) = match *direction {
+            Direction::Forward(n) => (i64::from(n), 0),
+            // Moving backwards retraces the path a forward move would have taken
+            Direction::Backward(n) => (-i64::from(n), 0),
+            Direction::Down(n) => (0, i64::from(n)),
+            Direction::Up(n) => (0, -i64::from(n)),
+        };
+
+        match part {
+            Part::Part1 => Location {
+                position,
+                depth: vertical,
+                aim: 0,
             },
-            Direction::Forward(n) => Location {
-                position: self.position + n,
-                depth: self.depth,
-                aim: self.aim,
+            Part::Part2 => Location {
+                position,
+                depth: 0,
+                aim: vertical,
             },
         }
     }
 
-    /// Produce a new location that is moved based on the current aim; depth is controlled by a multiple of aim
-    fn perform_aim_based_move(&self, direction: &Direction) -> Location {
-        match direction {
-            Direction::Up(n) => Location {
-                position: self.position,
-                depth: self.depth,
-                aim: self.aim - n,
-            },
-            Direction::Down(n) => Location {
-                position: self.position,
-                depth: self.depth,
-                aim: self.aim + n,
-            },
-            Direction::Forward(n) => Location {
-                position: self.position + n,
-                depth: self.depth + (self.aim * n),
-                aim: self.aim,
-            },
+    /// Treating both locations as moves from the origin, produce the location reached by performing this move and
+    /// then the other one. Any horizontal movement in the other move happens at this move's aim. Returns None if the
+    /// resulting location overflows.
+    fn then(&self, other: &Location) -> Option<Location> {
+        Some(Location {
+            position: self.position.checked_add(other.position)?,
+            depth: self
+                .depth
+                .checked_add(other.depth)?
+                .checked_add(self.aim.checked_mul(other.position)?)?,
+            aim: self.aim.checked_add(other.aim)?,
+        })
+    }
+
+    /// Produce the location reached by performing this move (from the origin) the given number of times, or None if
+    /// it overflows
+    fn repeated(&self, count: u32) -> Option<Location> {
+        // Repeatedly doubling the move means even huge counts only take a handful of steps
+        let mut result = Location::default();
+        let mut doubled = *self;
+        let mut remaining = count;
+        while remaining > 0 {
+            if remaining & 1 == 1 {
+                result = result.then(&doubled)?;
+            }
+
+            remaining >>= 1;
+            // Only double when it will be used again, so the final doubling can't report a spurious overflow
+            if remaining > 0 {
+                doubled = doubled.then(&doubled)?;
+            }
         }
+
+        Some(result)
     }
 }
 
-fn simulate(directions: &[Direction], part: &Part) -> i32 {
-    let final_location =
-        directions
-            .iter()
-            .fold(Location::default(), |memo, direction| match part {
-                Part::Part1 => memo.perform_directional_move(direction),
-                Part::Part2 => memo.perform_aim_based_move(direction),
-            });
+/// Move the sub through all of the given commands, starting from the origin
+fn final_location(commands: &[Command], part: &Part) -> Result<Location, OverflowError> {
+    commands
+        .iter()
+        .try_fold(Location::default(), |memo, command| {
+            let (reached, line_number) = match command {
+                Command::Move {
+                    direction,
+                    line_number,
+                } => (memo.then(&Location::of_move(direction, part)), *line_number),
+                Command::Repeat {
+                    count,
+                    body,
+                    line_number,
+                } => (
+                    final_location(body, part)?
+                        .repeated(*count)
+                        .and_then(|repeated| memo.then(&repeated)),
+                    *line_number,
+                ),
+            };
+
+            reached.ok_or(OverflowError { line_number })
+        })
+}
+
+fn simulate(commands: &[Command], part: &Part) -> Result<i128, OverflowError> {
+    let location = final_location(commands, part)?;
 
-    final_location.position * final_location.depth
+    // Both components fit in an i64, so their product always fits in an i128
+    Ok(i128::from(location.position) * i128::from(location.depth))
+}
+
+/// Get the direction that moves the sub by the given (signed) amount, using the forwards direction for positive amounts
+/// and the backwards direction for negative ones. Where the magnitude doesn't fit in a command (i.e. an amount of
+/// `-i32::MIN`), a negative magnitude in the opposite direction is used instead (e.g. `forward -2147483648`).
+fn signed_move(
+    n: i64,
+    forwards: fn(i32) -> Direction,
+    backwards: fn(i32) -> Direction,
+) -> Direction {
+    let (preferred, fallback) = if n >= 0 {
+        (forwards, backwards)
+    } else {
+        (backwards, forwards)
+    };
+
+    i32::try_from(n.abs())
+        .map(preferred)
+        .or_else(|_| i32::try_from(-n.abs()).map(fallback))
+        .unwrap_or_else(|_| panic!("{n} is too far to move in a single command"))
+}
+
+/// Get the direction that moves the sub horizontally by the given (signed) amount
+fn horizontal_move(n: i64) -> Direction {
+    signed_move(n, Direction::Forward, Direction::Backward)
+}
+
+/// Get the direction that moves the sub (or its aim) downwards by the given (signed) amount
+fn vertical_move(n: i64) -> Direction {
+    signed_move(n, Direction::Down, Direction::Up)
+}
+
+/// Produce the shortest list of directions that will move the sub from the origin to the given position and depth.
+/// Every command moves at most `2^31` (the magnitude of `i32::MIN`), which can always be written as a single command,
+/// so every target is reachable.
+fn plan_route(position: i32, depth: i32, part: &Part) -> Vec<Direction> {
+    // Work in i64, so that negating or dividing the target can't overflow
+    let (position, depth) = (i64::from(position), i64::from(depth));
+
+    let mut directions = Vec::new();
+    match part {
+        Part::Part1 => {
+            if position != 0 {
+                directions.push(horizontal_move(position));
+            }
+            if depth != 0 {
+                directions.push(vertical_move(depth));
+            }
+        }
+        // Depth only changes when moving horizontally with a non-zero aim, so getting to a depth will require
+        // at least one aim change followed by a horizontal move.
+        Part::Part2 if depth == 0 => {
+            if position != 0 {
+                directions.push(horizontal_move(position));
+            }
+        }
+        // With no net horizontal movement, we must go out and come back at a different aim
+        Part::Part2 if position == 0 => {
+            directions.push(horizontal_move(1));
+            directions.push(vertical_move(-depth));
+            directions.push(horizontal_move(-1));
+        }
+        Part::Part2 if depth % position == 0 => {
+            directions.push(vertical_move(depth / position));
+            directions.push(horizontal_move(position));
+        }
+        // Otherwise, move most of the way at zero aim, and then take a single unit step at an aim equal to the depth
+        Part::Part2 => {
+            let step = position.signum();
+            directions.push(horizontal_move(position - step));
+            directions.push(vertical_move(depth * step));
+            directions.push(horizontal_move(step));
+        }
+    }
+
+    directions
+}
+
+fn parse_magnitude(input: &str) -> IResult<&str, i32> {
+    map_res(recognize(pair(opt(char('-')), digit1)), str::parse)(input)
 }
 
 fn parse_line(line: &str) -> IResult<&str, Direction> {
     // using a parser combinator to split a string
     // https://i.imgur.com/B7bfMdE.jpg
     // (I really just want to get practice with nom because it's fun)
-    let parse_direction = alt((tag("forward"), tag("down"), tag("up")));
+    let parse_direction = alt((tag("forward"), tag("backward"), tag("down"), tag("up")));
     let (_, (raw_direction, magnitude)) = terminated(
-        separated_pair(
-            parse_direction,
-            tag(" "),
-            map_res(take_while1(|c: char| c.is_ascii_digit()), str::parse),
-        ),
+        separated_pair(parse_direction, space1, parse_magnitude),
         eof,
     )(line)?;
 
     let direction = match raw_direction {
         "forward" => Direction::Forward,
+        "backward" => Direction::Backward,
         "down" => Direction::Down,
         "up" => Direction::Up,
         _ => panic!("invalid direction returned by parser; this can't happen"),
@@ -112,27 +315,224 @@ fn parse_line(line: &str) -> IResult<&str, Direction> {
     Ok(("", direction))
 }
 
+fn parse_repeat_start(line: &str) -> IResult<&str, u32> {
+    terminated(
+        delimited(
+            pair(tag("repeat"), space1),
+            map_res(digit1, str::parse),
+            tuple((space0, char('{'))),
+        ),
+        eof,
+    )(line)
+}
+
+fn parse_statement(line: &str) -> IResult<&str, Statement> {
+    alt((
+        map(parse_repeat_start, Statement::RepeatStart),
+        map(terminated(char('}'), eof), |_| Statement::RepeatEnd),
+        map(parse_line, Statement::Move),
+    ))(line)
+}
+
+/// Strip any comment and surrounding whitespace from a line
+fn strip_comment(line: &str) -> &str {
+    line.split('#').next().unwrap_or_default().trim()
+}
+
+/// Split a line into its statements. Statements are separated by `;`, and the braces of a repeat block end the
+/// statement before them, so a block may be written on a single line (e.g. `repeat 3 { forward 1; down 2 }`).
+fn split_statements(line: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    for (i, c) in line.char_indices() {
+        match c {
+            ';' => {
+                statements.push(&line[start..i]);
+                start = i + 1;
+            }
+            '{' => {
+                statements.push(&line[start..=i]);
+                start = i + 1;
+            }
+            '}' => {
+                statements.push(&line[start..i]);
+                statements.push(&line[i..=i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    statements.push(&line[start..]);
+
+    statements
+        .into_iter()
+        .map(str::trim)
+        .filter(|statement| !statement.is_empty())
+        .collect()
+}
+
+/// Parse a full command script into its commands, keeping all repeat blocks as they are written.
+///
+/// Each statement is a move (`forward 5`), the start of a repeat block (`repeat 3 {`) or the end of the innermost
+/// block (`}`). Statements may be split across lines or written on the same line, separated by `;` where needed.
+fn parse_script<S: AsRef<str>>(lines: &[S]) -> Result<Vec<Command>, ParseError> {
+    let mut commands = Vec::new();
+    let mut open_blocks = Vec::<OpenBlock>::new();
+    for (i, raw_line) in lines.iter().enumerate() {
+        let line_number = i + 1;
+        for raw_statement in split_statements(strip_comment(raw_line.as_ref())) {
+            let (_, statement) = parse_statement(raw_statement).map_err(|err| ParseError {
+                line_number,
+                kind: ParseErrorKind::InvalidStatement(err.to_string()),
+            })?;
+
+            match statement {
+                Statement::Move(direction) => open_blocks
+                    .last_mut()
+                    .map_or(&mut commands, |block| &mut block.body)
+                    .push(Command::Move {
+                        direction,
+                        line_number,
+                    }),
+                Statement::RepeatStart(count) => open_blocks.push(OpenBlock {
+                    count,
+                    opened_on: line_number,
+                    body: Vec::new(),
+                }),
+                Statement::RepeatEnd => {
+                    let block = open_blocks.pop().ok_or(ParseError {
+                        line_number,
+                        kind: ParseErrorKind::UnmatchedBlockEnd,
+                    })?;
+
+                    open_blocks
+                        .last_mut()
+                        .map_or(&mut commands, |parent| &mut parent.body)
+                        .push(Command::Repeat {
+                            count: block.count,
+                            body: block.body,
+                            line_number: block.opened_on,
+                        });
+                }
+            }
+        }
+    }
+
+    match open_blocks.last() {
+        Some(block) => Err(ParseError {
+            line_number: block.opened_on,
+            kind: ParseErrorKind::UnclosedBlock,
+        }),
+        None => Ok(commands),
+    }
+}
+
+/// Print the shortest route to the given position and depth for both movement models
+fn print_routes(position: i32, depth: i32) {
+    for (name, part) in [("Part 1", Part::Part1), ("Part 2", Part::Part2)] {
+        let route = plan_route(position, depth, &part);
+
+        let commands = route
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(i, direction)| Command::Move {
+                direction,
+                line_number: i + 1,
+            })
+            .collect::<Vec<_>>();
+        let reached = final_location(&commands, &part)
+            .unwrap_or_else(|err| panic!("Planned route for {name} overflows ({err})"));
+        assert!(
+            reached.position == i64::from(position) && reached.depth == i64::from(depth),
+            "Planned route for {name} does not reach its target ({reached:?})"
+        );
+
+        println!("{name} ({} commands):", route.len());
+        for direction in route {
+            println!("{direction}");
+        }
+    }
+}
+
 fn main() {
-    let input_file_name = env::args().nth(1).expect("No input filename specified");
+    let args = env::args().collect::<Vec<_>>();
+    if args.get(1).map(String::as_str) == Some("--plan") {
+        let parse_arg = |idx: usize, name: &str| -> i32 {
+            args.get(idx)
+                .unwrap_or_else(|| panic!("No target {name} specified"))
+                .parse()
+                .unwrap_or_else(|err| panic!("Invalid target {name}: {err}"))
+        };
+
+        print_routes(parse_arg(2, "position"), parse_arg(3, "depth"));
+        return;
+    }
+
+    let input_file_name = args.get(1).expect("No input filename specified");
     let input_file = File::open(input_file_name).expect("Could not open input file");
-    let directions = BufReader::new(input_file)
+    let lines = BufReader::new(input_file)
         .lines()
-        .map(|res| res.expect("Failed to read line"))
-        .map(|line| {
-            let (remaining, direction) = parse_line(&line)
-                .unwrap_or_else(|err| panic!("Failed to parse line '{}': {}", line, err));
-
-            // Should never happen if the parse function succeeded
-            assert!(
-                remaining.is_empty(),
-                "Input remained after parsing: {}",
-                remaining
-            );
-
-            direction
-        })
-        .collect::<Vec<_>>();
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Failed to read input");
+    let commands =
+        parse_script(&lines).unwrap_or_else(|err| panic!("Failed to parse input: {err}"));
+
+    for (name, part) in [("Part 1", Part::Part1), ("Part 2", Part::Part2)] {
+        let answer = simulate(&commands, &part)
+            .unwrap_or_else(|err| panic!("Failed to simulate {name}: {err}"));
+        println!("{name}: {answer}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn planned_routes_reach_their_targets() {
+        let targets = [
+            (0, 0),
+            (15, 10),
+            (-7, 3),
+            (0, -12),
+            (5, 13),
+            (i32::MIN, 5),
+            (5, i32::MIN),
+            (-1, i32::MIN),
+            (i32::MAX, i32::MIN),
+            (i32::MIN, i32::MAX),
+        ];
 
-    println!("Part 1: {}", simulate(&directions, &Part::Part1));
-    println!("Part 2: {}", simulate(&directions, &Part::Part2));
+        for (position, depth) in targets {
+            for part in [Part::Part1, Part::Part2] {
+                let commands = plan_route(position, depth, &part)
+                    .into_iter()
+                    .map(|direction| Command::Move {
+                        direction,
+                        line_number: 0,
+                    })
+                    .collect::<Vec<_>>();
+                let reached = final_location(&commands, &part).unwrap();
+
+                assert_eq!(
+                    (reached.position, reached.depth),
+                    (i64::from(position), i64::from(depth))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn parse_errors_report_their_line() {
+        let line_of = |script: &str| match parse_script(&script.lines().collect::<Vec<_>>()) {
+            Ok(_) => panic!("script should not parse: {script:?}"),
+            Err(err) => err.line_number,
+        };
+
+        assert_eq!(line_of("forward 1\n\nsideways 2\n"), 3);
+        assert_eq!(line_of("repeat 2 {\n  up 1\n}\n}\n"), 4);
+        assert_eq!(line_of("forward 1\nrepeat 2 {\n  repeat 3 { down 1 }\n"), 2);
+        assert_eq!(line_of("# comment\nrepeat 2 { forward 1; jump 1 }\n"), 2);
+    }
 }
@@ -1,14 +1,20 @@
 #![warn(clippy::all, clippy::pedantic)]
+use num_bigint::BigUint;
 use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::ops::AddAssign;
 use thiserror::Error;
 
+const WORD_SIZE: usize = u64::BITS as usize;
+
 #[derive(Debug, Error)]
 enum Error {
     #[error("Unexpected char '{0}'")]
     InvalidChar(char),
+    #[error("Report has width {actual}, but previous reports had width {expected}")]
+    MismatchedWidth { expected: usize, actual: usize },
+    #[error("No reports were given")]
+    NoReports,
 }
 
 struct MinMax<T> {
@@ -17,106 +23,152 @@ struct MinMax<T> {
 }
 
 #[derive(Debug)]
-struct BitCounts(u32, u32);
-
-impl TryFrom<char> for BitCounts {
-    type Error = Error;
-    fn try_from(c: char) -> Result<Self, Self::Error> {
-        match c {
-            '0' => Ok(Self(1, 0)),
-            '1' => Ok(Self(0, 1)),
-            _ => Err(Error::InvalidChar(c)),
-        }
-    }
-}
-
-// Just doing AddAssign for &mut BitCounts (hell, not even implementing Add) is a bit improper (we should implement
-// all permutations), but I don't care enough just for this problem.
-impl AddAssign<BitCounts> for &mut BitCounts {
-    fn add_assign(&mut self, rhs: BitCounts) {
-        self.0 += rhs.0;
-        self.1 += rhs.1;
-    }
-}
+struct BitCounts(usize, usize);
 
 impl BitCounts {
     /// Get the more common bit of the two
     fn more_common_bit(&self) -> u8 {
-        if self.0 > self.1 {
-            0_u8
-        } else {
-            1_u8
-        }
+        u8::from(self.0 <= self.1)
     }
 
     /// Get the less common bit of the two
     fn less_common_bit(&self) -> u8 {
-        if self.more_common_bit() == 0_u8 {
-            1_u8
-        } else {
-            0_u8
+        1 - self.more_common_bit()
+    }
+
+    /// Check if both bits are equally common
+    fn is_tie(&self) -> bool {
+        self.0 == self.1
+    }
+}
+
+/// A single line of the diagnostic report, packed into 64 bit words.
+///
+/// The bits are packed left-aligned: the first bit of the line is the most significant bit of the first word, and
+/// any unused bits at the end of the last word are zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Report {
+    words: Vec<u64>,
+    width: usize,
+}
+
+impl TryFrom<&str> for Report {
+    type Error = Error;
+    fn try_from(bit_string: &str) -> Result<Self, Self::Error> {
+        let width = bit_string.len();
+        let mut words = vec![0_u64; width.div_ceil(WORD_SIZE)];
+        for (i, c) in bit_string.chars().enumerate() {
+            let bit = match c {
+                '0' => 0,
+                '1' => 1,
+                _ => return Err(Error::InvalidChar(c)),
+            };
+
+            words[i / WORD_SIZE] |= bit << (WORD_SIZE - 1 - i % WORD_SIZE);
         }
+
+        Ok(Self { words, width })
     }
 }
 
-/// Calculate a rate for the puzzle output
-fn calculate_rate(bits: &[u8]) -> u32 {
-    #[allow(clippy::cast_possible_truncation)]
-    bits.iter()
-        .rev()
-        .enumerate()
-        .fold(0_u32, |total, (i, &bit)| {
-            // This cast is technically a truncation but I know there's a small enough number of elements
-            total + (u32::from(bit) * 2_u32.pow(i as u32))
-        })
+impl Report {
+    /// Get the bit at the given index, where index 0 is the leftmost bit
+    fn bit(&self, index: usize) -> u8 {
+        assert!(index < self.width, "bit index out of range");
+        let word = self.words[index / WORD_SIZE];
+        u8::from((word >> (WORD_SIZE - 1 - index % WORD_SIZE)) & 1 == 1)
+    }
 }
 
-/// Convert a string to a Vec of bits
-fn string_to_bit_vec(bit_string: &str) -> Vec<u8> {
-    bit_string
-        .chars()
-        .map(|c| match c {
-            '0' => 0,
-            '1' => 1,
-            _ => panic!("Invalid binary string"),
-        })
-        .collect()
+#[derive(Debug, Default)]
+struct TrieNode {
+    /// The number of reports whose prefix ends at (or passes through) this node
+    count: usize,
+    /// The indices of the nodes for the next bit being zero or one, respectively
+    children: [Option<usize>; 2],
 }
 
-fn update_bit_count_vec_for_bit_string(
-    bit_counts: &mut Vec<BitCounts>,
-    bit_string: &str,
-) -> Result<(), Error> {
-    bit_string.chars().enumerate().try_for_each(|(i, c)| {
-        match bit_counts.get_mut(i) {
-            None => {
-                let element = BitCounts::try_from(c)?;
-                bit_counts.push(element);
-            }
-            Some(mut current_count) => {
-                current_count += BitCounts::try_from(c)?;
+/// A binary trie of all reports, where the nth level of the trie represents the nth bit of the report
+#[derive(Debug)]
+struct ReportTrie {
+    nodes: Vec<TrieNode>,
+    width: usize,
+}
+
+impl ReportTrie {
+    const ROOT: usize = 0;
+
+    fn new(reports: &[Report], width: usize) -> Self {
+        let mut nodes = vec![TrieNode::default()];
+        for report in reports {
+            let mut current = Self::ROOT;
+            nodes[current].count += 1;
+            for bit_index in 0..width {
+                let bit = usize::from(report.bit(bit_index));
+                current = if let Some(child) = nodes[current].children[bit] {
+                    child
+                } else {
+                    nodes.push(TrieNode::default());
+                    let child = nodes.len() - 1;
+                    nodes[current].children[bit] = Some(child);
+                    child
+                };
+
+                nodes[current].count += 1;
             }
         }
-        Ok(())
-    })?;
 
-    Ok(())
+        Self { nodes, width }
+    }
+
+    /// Get the number of reports that pass through the given child of a node
+    fn child_count(&self, node: usize, bit: usize) -> usize {
+        self.nodes[node].children[bit].map_or(0, |child| self.nodes[child].count)
+    }
+}
+
+/// Parse all of the reports, ensuring they all have the same width
+fn parse_reports<S: AsRef<str>>(bit_strings: &[S]) -> Result<Vec<Report>, Error> {
+    let reports = bit_strings
+        .iter()
+        .map(|line| Report::try_from(line.as_ref()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let expected = reports.first().ok_or(Error::NoReports)?.width;
+    if let Some(mismatched) = reports.iter().find(|report| report.width != expected) {
+        return Err(Error::MismatchedWidth {
+            expected,
+            actual: mismatched.width,
+        });
+    }
+
+    Ok(reports)
 }
 
-/// Count the number of bits in each position for every bit string. The return value is the number of zeroes and ones
+/// Calculate a rate for the puzzle output
+fn calculate_rate(bits: &[u8]) -> BigUint {
+    bits.iter().fold(BigUint::default(), |total, &bit| {
+        (total << 1_u8) + BigUint::from(bit)
+    })
+}
+
+/// Count the number of bits in each position for every report. The return value is the number of zeroes and ones
 /// in each position
-fn count_bits<S: AsRef<str>>(bit_strings: &[S]) -> Result<Vec<BitCounts>, Error> {
-    bit_strings.iter().try_fold(
-        Vec::<BitCounts>::new(),
-        |mut counts, line| -> Result<_, Error> {
-            update_bit_count_vec_for_bit_string(&mut counts, line.as_ref())?;
-            Ok(counts)
-        },
-    )
+fn count_bits(reports: &[Report], width: usize) -> Vec<BitCounts> {
+    (0..width)
+        .map(|bit_index| {
+            let ones = reports
+                .iter()
+                .filter(|report| report.bit(bit_index) == 1)
+                .count();
+
+            BitCounts(reports.len() - ones, ones)
+        })
+        .collect()
 }
 
-fn part1(input_lines: &[String]) -> u32 {
-    let bit_counts = count_bits(input_lines).expect("Failed to count bits");
+fn part1(reports: &[Report]) -> BigUint {
+    let bit_counts = count_bits(reports, reports[0].width);
 
     let most_common_bits = bit_counts
         .iter()
@@ -134,102 +186,172 @@ fn part1(input_lines: &[String]) -> u32 {
     gamma_rate * epsilon_rate
 }
 
-/// Calculate a rating (part 2), using the bit returned by `get_bit` to determine if an element should be discarded
-fn calculate_part2_rating<F>(input_lines: &[String], get_bit: F) -> Result<u32, Error>
+/// A single step of filtering the candidates for a part 2 rating
+#[derive(Debug)]
+struct FilterStep {
+    bit_index: usize,
+    /// The bit counts among the candidates before filtering
+    counts: BitCounts,
+    kept_bit: u8,
+    /// The number of candidates remaining after filtering
+    remaining: usize,
+}
+
+/// A part 2 rating, along with every step taken to filter down to it
+struct Rating {
+    value: BigUint,
+    steps: Vec<FilterStep>,
+}
+
+/// Calculate a rating (part 2), using the bit returned by `get_bit` to determine which branch of the trie should be
+/// kept at each level
+fn calculate_part2_rating<F>(trie: &ReportTrie, get_bit: F) -> Rating
 where
     F: Fn(MinMax<u8>) -> u8,
 {
-    let mut remaining_values = input_lines
-        .iter()
-        .map(String::as_str)
-        .collect::<Vec<&str>>();
+    let mut bits = Vec::with_capacity(trie.width);
+    let mut steps = Vec::new();
+    let mut current = ReportTrie::ROOT;
+    for bit_index in 0..trie.width {
+        let count_at_bit_index =
+            BitCounts(trie.child_count(current, 0), trie.child_count(current, 1));
 
-    for bit_index in 0..input_lines[0].len() {
-        // Puzzle states that this is the halting point
-        if remaining_values.len() == 1 {
-            break;
-        }
-
-        let remaining_bit_counts = count_bits(&remaining_values)?;
-        let count_at_bit_index = &remaining_bit_counts[bit_index];
-
-        remaining_values.retain(|val| {
-            // We know these will be zero or one... you can't truncate here
-            #[allow(clippy::cast_possible_truncation)]
-            let bit_at_position: u8 =
-                val.chars()
-                    .nth(bit_index)
-                    // If either of these fail, our counter must have hit a serious problem
-                    .expect("number of bits is different than counted number of bits")
-                    .to_digit(2)
-                    .expect("binary number did not have binary digit") as u8;
-
-            let bit_to_compare = get_bit(MinMax {
+        // Once there is a single value left (which the puzzle states is the halting point), there is only one branch
+        // to follow, so we just walk it to recover the rest of the value. The same goes for if every remaining value
+        // shares this bit, regardless of what `get_bit` asks for.
+        let bit = match trie.nodes[current].children {
+            [Some(_), Some(_)] => get_bit(MinMax {
                 min: count_at_bit_index.less_common_bit(),
                 max: count_at_bit_index.more_common_bit(),
+            }),
+            [Some(_), _] => 0,
+            [None, Some(_)] => 1,
+            [None, None] => panic!("trie node has no children before reaching the report width"),
+        };
+
+        let num_candidates = trie.nodes[current].count;
+        bits.push(bit);
+        current = trie.nodes[current].children[usize::from(bit)]
+            .expect("chosen bit must have a child in the trie");
+
+        if num_candidates > 1 {
+            steps.push(FilterStep {
+                bit_index,
+                counts: count_at_bit_index,
+                kept_bit: bit,
+                remaining: trie.nodes[current].count,
             });
+        }
+    }
 
-            bit_at_position == bit_to_compare
-        });
+    Rating {
+        value: calculate_rate(&bits),
+        steps,
     }
+}
 
-    // Stated by puzzle
-    assert!(
-        remaining_values.len() == 1,
-        "Only one item should remain: {:?}",
-        &remaining_values
-    );
-
-    // Yes, we allocate here unnecessarily (we could just count in the string chars directly), but _shrug_.
-    // I didn't want to duplicate the logic just to avoid it for such a simple problem
-    let bit_vec = string_to_bit_vec(remaining_values[0]);
-    Ok(calculate_rate(&bit_vec))
-}
-
-fn part2(input_lines: &[String]) -> u32 {
-    let oxygen_rating = calculate_part2_rating(
-        input_lines,
-        |MinMax {
-             min: least_common,
-             max: most_common,
-         }| {
-            if most_common == least_common {
-                1
-            } else {
-                most_common
-            }
-        },
-    )
-    .expect("Failed to calculate oxygen rating");
-
-    let co2_rating = calculate_part2_rating(
-        input_lines,
-        |MinMax {
-             min: least_common,
-             max: most_common,
-         }| {
-            if most_common == least_common {
-                0
-            } else {
-                least_common
-            }
-        },
-    )
-    .expect("Failed to calculate CO2 rating");
+fn oxygen_bit(
+    MinMax {
+        min: least_common,
+        max: most_common,
+    }: MinMax<u8>,
+) -> u8 {
+    if most_common == least_common {
+        1
+    } else {
+        most_common
+    }
+}
+
+fn co2_bit(
+    MinMax {
+        min: least_common,
+        max: most_common,
+    }: MinMax<u8>,
+) -> u8 {
+    if most_common == least_common {
+        0
+    } else {
+        least_common
+    }
+}
+
+/// The ratings found for part 2, whose product is the life support rating
+struct LifeSupportRatings {
+    oxygen: Rating,
+    co2: Rating,
+}
 
-    oxygen_rating * co2_rating
+impl LifeSupportRatings {
+    fn life_support_rating(&self) -> BigUint {
+        &self.oxygen.value * &self.co2.value
+    }
+}
+
+fn part2(reports: &[Report]) -> LifeSupportRatings {
+    let trie = ReportTrie::new(reports, reports[0].width);
+
+    LifeSupportRatings {
+        oxygen: calculate_part2_rating(&trie, oxygen_bit),
+        co2: calculate_part2_rating(&trie, co2_bit),
+    }
+}
+
+/// Print the steps taken to find a part 2 rating
+fn print_rating_steps(name: &str, rating: &Rating) {
+    println!("{name} rating: {}", rating.value);
+    println!("  bit  zeroes    ones  kept  remaining");
+    for step in &rating.steps {
+        println!(
+            "  {:>3}  {:>6}  {:>6}  {:>4}  {:>9}{}",
+            step.bit_index,
+            step.counts.0,
+            step.counts.1,
+            step.kept_bit,
+            step.remaining,
+            if step.counts.is_tie() { "  (tie)" } else { "" }
+        );
+    }
+}
+
+/// Print the full per-position breakdown of the diagnostic report, including how each tie was resolved in the given
+/// part 2 ratings
+fn print_diagnostic_report(reports: &[Report], ratings: &LifeSupportRatings) {
+    let bit_counts = count_bits(reports, reports[0].width);
+    println!("Bit counts:");
+    println!("  bit  zeroes    ones  gamma  epsilon");
+    for (bit_index, counts) in bit_counts.iter().enumerate() {
+        println!(
+            "  {:>3}  {:>6}  {:>6}  {:>5}  {:>7}{}",
+            bit_index,
+            counts.0,
+            counts.1,
+            counts.more_common_bit(),
+            counts.less_common_bit(),
+            if counts.is_tie() { "  (tie)" } else { "" }
+        );
+    }
+
+    print_rating_steps("Oxygen", &ratings.oxygen);
+    print_rating_steps("CO2", &ratings.co2);
 }
 
 fn main() {
     let input_file_name = env::args().nth(1).expect("No input filename specified");
     let input_file = File::open(input_file_name).expect("Could not open input file");
 
-    // vec of the count of each bit, by position
     let input_lines = BufReader::new(input_file)
         .lines()
         .map(|res| res.expect("Failed to read line"))
         .collect::<Vec<String>>();
 
-    println!("Part 1: {}", part1(&input_lines));
-    println!("Part 2: {}", part2(&input_lines));
+    let reports = parse_reports(&input_lines).expect("Failed to parse reports");
+
+    println!("Part 1: {}", part1(&reports));
+    let ratings = part2(&reports);
+    println!("Part 2: {}", ratings.life_support_rating());
+
+    if env::args().nth(2).as_deref() == Some("--report") {
+        print_diagnostic_report(&reports, &ratings);
+    }
 }
@@ -1,20 +1,41 @@
 #![warn(clippy::all, clippy::pedantic)]
 
 use nom::{
+    branch::alt,
     bytes::complete::{tag, take_while1},
-    character::complete::char,
-    combinator::{eof, fail, map_res, opt},
-    multi::{many0, separated_list1},
+    character::complete::{char, not_line_ending, space0, space1},
+    combinator::{eof, fail, map_res, value},
+    multi::{many0, many1, separated_list1},
     sequence::{preceded, separated_pair, terminated, tuple},
     IResult,
 };
-use std::collections::VecDeque;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
 use std::env;
 use std::fmt;
 use std::fmt::{Debug, Display, Formatter};
-use std::fs;
-
-const BOARD_SIZE: usize = 5;
+use std::fs::{self, File};
+use std::io;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+enum Error {
+    #[error("Unknown win rule '{0}'")]
+    UnknownRule(String),
+    #[error("Diagonal wins require a square board, but boards are {0}x{1}")]
+    NonSquareDiagonal(usize, usize),
+    #[error("Pattern '{name}' is {pattern_rows}x{pattern_cols}, but boards are {board_rows}x{board_cols}")]
+    MismatchedPattern {
+        name: String,
+        pattern_rows: usize,
+        pattern_cols: usize,
+        board_rows: usize,
+        board_cols: usize,
+    },
+    #[error("Pattern '{0}' has no tiles, so every board would win immediately")]
+    EmptyPattern(String),
+}
 
 #[derive(Debug, Clone, Copy)]
 enum BingoTile {
@@ -22,8 +43,9 @@ enum BingoTile {
     Marked(u8),
 }
 
+/// A bingo board, stored as a list of rows. Every row has the same number of tiles.
 #[derive(Clone)]
-struct BingoBoard([[BingoTile; BOARD_SIZE]; BOARD_SIZE]);
+struct BingoBoard(Vec<Vec<BingoTile>>);
 
 #[derive(Debug, Clone)]
 struct Input {
@@ -31,16 +53,98 @@ struct Input {
     boards: Vec<BingoBoard>,
 }
 
-/// `BoardState` indicates whether or not a board has won
+/// A named set of tiles (as row/column pairs) that win the game once every one of them has been marked
+#[derive(Debug, Clone)]
+struct WinPattern {
+    name: String,
+    tiles: Vec<(usize, usize)>,
+}
+
+/// A pattern loaded from a pattern file, which must have the same dimensions as the boards it is used with
+#[derive(Debug, Clone)]
+struct CustomPattern {
+    name: String,
+    grid: Vec<Vec<bool>>,
+}
+
+/// A rule describing one or more ways a board can win
+#[derive(Debug, Clone)]
+enum WinRule {
+    /// Any full row or column (the standard rules)
+    Lines,
+    /// Either full diagonal; only valid for square boards
+    Diagonals,
+    FourCorners,
+    /// Every tile on the board
+    Blackout,
+    Custom(Vec<CustomPattern>),
+}
+
+/// A board that has won, along with the name of the pattern it won with
+#[derive(Debug, Clone)]
+struct Win {
+    /// The index of the board in the game's input
+    board_idx: usize,
+    board: BingoBoard,
+    pattern_name: String,
+    /// The index of the winning call in the game's list of calls
+    turn: usize,
+}
+
+/// How a single board fared over a complete game. All fields other than the board index are empty if the board
+/// never won.
+#[derive(Debug, Serialize)]
+struct BoardReport {
+    board: usize,
+    /// The board's place in the finishing order, starting at 1. Boards that win on the same call share a place.
+    position: Option<usize>,
+    winning_call: Option<u8>,
+    turn: Option<usize>,
+    score: Option<u64>,
+    pattern: Option<String>,
+}
+
+/// The outcome of many games played with the same boards, but with shuffled calls
+#[derive(Debug, Default, Clone)]
+struct SimulationStats {
+    /// The number of games in which this board was among the first to win
+    first_wins: u32,
+    /// The number of games in which this board was among the last to win
+    last_wins: u32,
+    /// The number of games in which this board won at all
+    wins: u32,
+    /// The sum of the winning turn over every game this board won
+    total_winning_turns: usize,
+}
+
+/// The format to export a `BoardReport` list in
+#[derive(Debug, Clone, Copy)]
+enum ReportFormat {
+    Csv,
+    Json,
+}
+
+/// `BoardState` indicates whether or not a board has won, and tracks how close it is to winning
 struct BoardState {
     won: bool,
     board: BingoBoard,
+    /// The positions of every number on the board that has not yet been called
+    positions: HashMap<u8, Vec<(usize, usize)>>,
+    /// The number of unmarked tiles in each of the game's win patterns
+    unmarked_counts: Vec<usize>,
 }
 
 /// `BingoGame` represents an active game of bingo and its state.
 struct BingoGame {
     calls: VecDeque<u8>,
+    /// The number of calls that have been made so far
+    num_calls_made: usize,
     boards: Vec<BoardState>,
+    win_patterns: Vec<WinPattern>,
+    /// The indices of every win pattern that each tile is a part of, indexed by row and column
+    patterns_by_tile: Vec<Vec<Vec<usize>>>,
+    /// The indices of every board that each number appears on, so that a call only needs to visit those boards
+    boards_by_number: HashMap<u8, Vec<usize>>,
 }
 
 /// `BingoPlayer` is an iterator that will iterate over the successive winners of a `BingoGame`
@@ -67,9 +171,9 @@ impl Display for BingoTile {
 
 impl Debug for BingoBoard {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for row in self.0 {
+        for row in &self.0 {
             for tile in row {
-                write!(f, "{:2} ", tile)?;
+                write!(f, "{tile:2} ")?;
             }
 
             writeln!(f)?;
@@ -79,106 +183,271 @@ impl Debug for BingoBoard {
     }
 }
 
-impl From<Input> for BingoGame {
-    /// Start a new game from the given puzzle input
-    fn from(input: Input) -> Self {
+impl TryFrom<&str> for WinRule {
+    type Error = Error;
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        match name {
+            "lines" => Ok(Self::Lines),
+            "diagonals" => Ok(Self::Diagonals),
+            "corners" => Ok(Self::FourCorners),
+            "blackout" => Ok(Self::Blackout),
+            _ => Err(Error::UnknownRule(name.to_string())),
+        }
+    }
+}
+
+impl WinRule {
+    /// Get all of the patterns this rule allows to win on a board with the given number of rows and columns
+    fn patterns(&self, (num_rows, num_cols): (usize, usize)) -> Result<Vec<WinPattern>, Error> {
+        let patterns = match self {
+            Self::Lines => {
+                let rows = (0..num_rows).map(|row| WinPattern {
+                    name: format!("row {}", row + 1),
+                    tiles: (0..num_cols).map(|col| (row, col)).collect(),
+                });
+                let cols = (0..num_cols).map(|col| WinPattern {
+                    name: format!("column {}", col + 1),
+                    tiles: (0..num_rows).map(|row| (row, col)).collect(),
+                });
+
+                rows.chain(cols).collect()
+            }
+            Self::Diagonals => {
+                if num_rows != num_cols {
+                    return Err(Error::NonSquareDiagonal(num_rows, num_cols));
+                }
+
+                vec![
+                    WinPattern {
+                        name: "diagonal".to_string(),
+                        tiles: (0..num_rows).map(|i| (i, i)).collect(),
+                    },
+                    WinPattern {
+                        name: "anti-diagonal".to_string(),
+                        tiles: (0..num_rows).map(|i| (i, num_cols - 1 - i)).collect(),
+                    },
+                ]
+            }
+            Self::FourCorners => vec![WinPattern {
+                name: "four corners".to_string(),
+                tiles: vec![
+                    (0, 0),
+                    (0, num_cols - 1),
+                    (num_rows - 1, 0),
+                    (num_rows - 1, num_cols - 1),
+                ],
+            }],
+            Self::Blackout => vec![WinPattern {
+                name: "blackout".to_string(),
+                tiles: (0..num_rows)
+                    .flat_map(|row| (0..num_cols).map(move |col| (row, col)))
+                    .collect(),
+            }],
+            Self::Custom(custom_patterns) => custom_patterns
+                .iter()
+                .map(|pattern| pattern.to_win_pattern((num_rows, num_cols)))
+                .collect::<Result<_, _>>()?,
+        };
+
+        Ok(patterns)
+    }
+}
+
+impl CustomPattern {
+    /// Convert this pattern to a `WinPattern`, ensuring it fits the given board dimensions
+    fn to_win_pattern(&self, (num_rows, num_cols): (usize, usize)) -> Result<WinPattern, Error> {
+        let pattern_rows = self.grid.len();
+        let pattern_cols = self.grid.first().map_or(0, Vec::len);
+        if (pattern_rows, pattern_cols) != (num_rows, num_cols) {
+            return Err(Error::MismatchedPattern {
+                name: self.name.clone(),
+                pattern_rows,
+                pattern_cols,
+                board_rows: num_rows,
+                board_cols: num_cols,
+            });
+        }
+
+        let tiles = self
+            .grid
+            .iter()
+            .enumerate()
+            .flat_map(|(row, grid_row)| {
+                grid_row
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &set)| set)
+                    .map(move |(col, _)| (row, col))
+            })
+            .collect::<Vec<_>>();
+
+        if tiles.is_empty() {
+            return Err(Error::EmptyPattern(self.name.clone()));
+        }
+
+        Ok(WinPattern {
+            name: self.name.clone(),
+            tiles,
+        })
+    }
+}
+
+impl BingoGame {
+    /// Start a new game from the given puzzle input, where boards can win by any of the given rules
+    fn new(input: Input, rules: &[WinRule]) -> Result<Self, Error> {
+        let dimensions = input.boards[0].dimensions();
+        let win_patterns = rules
+            .iter()
+            .map(|rule| rule.patterns(dimensions))
+            .collect::<Result<Vec<_>, _>>()?
+            .concat();
+
+        let (num_rows, num_cols) = dimensions;
+        let mut patterns_by_tile = vec![vec![vec![]; num_cols]; num_rows];
+        for (i, pattern) in win_patterns.iter().enumerate() {
+            for &(row, col) in &pattern.tiles {
+                patterns_by_tile[row][col].push(i);
+            }
+        }
+
         let calls = VecDeque::from(input.calls);
         let boards = input
             .boards
             .into_iter()
-            .map(|board| BoardState { won: false, board })
-            .collect();
+            .map(|board| BoardState::new(board, &win_patterns))
+            .collect::<Vec<_>>();
+
+        let mut boards_by_number = HashMap::<u8, Vec<usize>>::new();
+        for (i, board_state) in boards.iter().enumerate() {
+            for &n in board_state.positions.keys() {
+                boards_by_number.entry(n).or_default().push(i);
+            }
+        }
 
-        Self { calls, boards }
+        Ok(Self {
+            calls,
+            num_calls_made: 0,
+            boards,
+            win_patterns,
+            patterns_by_tile,
+            boards_by_number,
+        })
     }
-}
 
-impl BingoGame {
     /// Return an iterator to play this bingo game
-    fn play(&mut self) -> BingoPlayer {
+    fn play(&mut self) -> BingoPlayer<'_> {
         BingoPlayer { game: self }
     }
 }
 
 impl BingoBoard {
-    /// Check if this board has won the game
-    fn is_winner(&self) -> bool {
-        assert_eq!(
-            self.0.len(),
-            BOARD_SIZE,
-            "Board number of rows doesn't match expected size"
-        );
-        assert_eq!(
-            self.0[0].len(),
-            BOARD_SIZE,
-            "Board number of cols doesn't match expected size"
-        );
+    /// Get the number of rows and columns on this board, respectively
+    fn dimensions(&self) -> (usize, usize) {
+        (self.0.len(), self.0.first().map_or(0, Vec::len))
+    }
+}
 
-        for col in 0..BOARD_SIZE {
-            let mut won_by_col = true;
-            for row in 0..BOARD_SIZE {
-                let won_by_row = self.0[row]
-                    .iter()
-                    .all(|item| matches!(item, BingoTile::Marked(_)));
-                if won_by_row {
-                    return true;
-                } else if !matches!(self.0[row][col], BingoTile::Marked(_)) {
-                    won_by_col = false;
-                    break;
+impl BoardState {
+    fn new(board: BingoBoard, win_patterns: &[WinPattern]) -> Self {
+        let mut positions = HashMap::<u8, Vec<(usize, usize)>>::new();
+        for (row, board_row) in board.0.iter().enumerate() {
+            for (col, &tile) in board_row.iter().enumerate() {
+                if let BingoTile::Unmarked(n) = tile {
+                    positions.entry(n).or_default().push((row, col));
                 }
             }
-            if won_by_col {
-                return true;
-            }
         }
 
-        false
+        let unmarked_counts = win_patterns
+            .iter()
+            .map(|pattern| {
+                let count = pattern
+                    .tiles
+                    .iter()
+                    .filter(|&&(row, col)| matches!(board.0[row][col], BingoTile::Unmarked(_)))
+                    .count();
+
+                // A pattern with nothing left to mark could never be completed by a call; empty patterns are
+                // rejected when the rules are set up
+                debug_assert!(
+                    count > 0,
+                    "pattern '{}' has no unmarked tiles",
+                    pattern.name
+                );
+                count
+            })
+            .collect();
+
+        Self {
+            won: false,
+            board,
+            positions,
+            unmarked_counts,
+        }
     }
 
-    /// Mark the given number on the board, if it exists
-    fn mark_n(&mut self, n: u8) {
-        for row in &mut self.0 {
-            for tile in row {
-                if let BingoTile::Unmarked(tile_n) = tile {
-                    if *tile_n == n {
-                        *tile = BingoTile::Marked(n);
-                    }
+    /// Mark the given number on the board, if it exists. Returns the index of the win pattern that marking this number
+    /// completed, if any. If more than one pattern was completed, the one that comes first is returned.
+    fn mark_n(&mut self, n: u8, patterns_by_tile: &[Vec<Vec<usize>>]) -> Option<usize> {
+        let mut completed_pattern = None;
+        for (row, col) in self.positions.remove(&n).unwrap_or_default() {
+            self.board.0[row][col] = BingoTile::Marked(n);
+            for &pattern_idx in &patterns_by_tile[row][col] {
+                self.unmarked_counts[pattern_idx] -= 1;
+                if self.unmarked_counts[pattern_idx] == 0 {
+                    completed_pattern = Some(
+                        completed_pattern.map_or(pattern_idx, |idx: usize| idx.min(pattern_idx)),
+                    );
                 }
             }
         }
+
+        completed_pattern
     }
 }
 
-impl<'a> Iterator for BingoPlayer<'a> {
+impl Iterator for BingoPlayer<'_> {
     // Yields the winning call and all of the boards that won with that call
-    type Item = (u8, Vec<BingoBoard>);
+    type Item = (u8, Vec<Win>);
 
     fn next(&mut self) -> Option<Self::Item> {
         while let Some(call) = self.game.calls.pop_front() {
+            let turn = self.game.num_calls_made;
+            self.game.num_calls_made += 1;
+
             let boards = &mut self.game.boards;
-            let mut winning_boards: Vec<BingoBoard> = vec![];
-            for BoardState {
-                won: board_has_won,
-                board,
-            } in boards.iter_mut()
-            {
+            let win_patterns = &self.game.win_patterns;
+            let patterns_by_tile = &self.game.patterns_by_tile;
+            let board_indices = self
+                .game
+                .boards_by_number
+                .get(&call)
+                .map_or(&[][..], Vec::as_slice);
+
+            let mut winning_boards: Vec<Win> = vec![];
+            for &board_idx in board_indices {
+                let board_state = &mut boards[board_idx];
                 // If a board has one, do not consider it as an item to check.
                 // This prevents winning boards from attempting to win more than once.
-                if *board_has_won {
+                if board_state.won {
                     continue;
                 }
 
-                board.mark_n(call);
-                if board.is_winner() {
+                if let Some(pattern_idx) = board_state.mark_n(call, patterns_by_tile) {
                     // We should mark all boards, and not return immediately, so that an early board winning
                     // does not ruin the winners for everyone else
                     //
                     // Also, more than one board can win in the same turn (I've seen it happen!)
-                    winning_boards.push(board.clone());
+                    winning_boards.push(Win {
+                        board_idx,
+                        board: board_state.board.clone(),
+                        pattern_name: win_patterns[pattern_idx].name.clone(),
+                        turn,
+                    });
 
                     // We could probably remove the board from the boards vec, but for debugging, this changes the
                     // indexes, which makes it difficult to follow the continuity of boards
-                    *board_has_won = true;
+                    board_state.won = true;
                 }
             }
 
@@ -202,33 +471,24 @@ fn parse_bingo_calls(calls_line: &str) -> IResult<&str, Vec<u8>> {
 fn parse_bingo_board(input_chunk: &str) -> IResult<&str, BingoBoard> {
     let (remaining, raw_board) = separated_list1(
         char('\n'),
-        separated_list1(char(' '), preceded(opt(char(' ')), parse_bingo_number)),
+        preceded(space0, separated_list1(space1, parse_bingo_number)),
     )(input_chunk)?;
 
-    // If we didn't get the correct board back from reading, this board is not parsable.
-    if raw_board.len() != BOARD_SIZE || raw_board[0].len() != BOARD_SIZE {
+    // If every row isn't the same length, this board is not parsable.
+    if raw_board.iter().any(|row| row.len() != raw_board[0].len()) {
         return fail(input_chunk);
     }
 
-    let mut board = [[BingoTile::Unmarked(0_u8); BOARD_SIZE]; BOARD_SIZE];
-    for i in 0..board.len() {
-        let board_row = &mut board[i];
-        let raw_board_row = &raw_board[i];
-        assert_eq!(
-            board_row.len(),
-            raw_board_row.len(),
-            "board size was allocated incorrectly for the board, or an invalid board was passed"
-        );
-        for j in 0..board_row.len() {
-            board_row[j] = BingoTile::Unmarked(raw_board_row[j]);
-        }
-    }
+    let board = raw_board
+        .into_iter()
+        .map(|row| row.into_iter().map(BingoTile::Unmarked).collect())
+        .collect();
 
     Ok((remaining, BingoBoard(board)))
 }
 
 // Calculate the score of a winning board, which is the same for both parts
-fn calculate_score(winning_board: &BingoBoard, winning_call: u32) -> u32 {
+fn calculate_score(winning_board: &BingoBoard, winning_call: u64) -> u64 {
     let unmarked_tiles_iter = winning_board
         .0
         .iter()
@@ -238,12 +498,12 @@ fn calculate_score(winning_board: &BingoBoard, winning_call: u32) -> u32 {
             BingoTile::Unmarked(n) | BingoTile::Marked(n) => n,
         });
 
-    unmarked_tiles_iter.map(u32::from).sum::<u32>() * winning_call
+    unmarked_tiles_iter.map(u64::from).sum::<u64>() * winning_call
 }
 
-fn part1(input: &Input) -> u32 {
-    let mut game = BingoGame::from(input.clone());
-    let (winning_call, winning_board) = game
+fn part1(input: &Input, rules: &[WinRule]) -> (u64, String) {
+    let mut game = BingoGame::new(input.clone(), rules).expect("Failed to set up game");
+    let (winning_call, win) = game
         .play()
         .next()
         .map(|(winning_call, winning_boards)| {
@@ -257,12 +517,15 @@ fn part1(input: &Input) -> u32 {
         })
         .expect("Puzzle produced no winner for any bingo boards");
 
-    calculate_score(&winning_board, winning_call.into())
+    (
+        calculate_score(&win.board, winning_call.into()),
+        win.pattern_name,
+    )
 }
 
-fn part2(input: &Input) -> u32 {
-    let mut game = BingoGame::from(input.clone());
-    let (winning_call, winning_board) = game
+fn part2(input: &Input, rules: &[WinRule]) -> (u64, String) {
+    let mut game = BingoGame::new(input.clone(), rules).expect("Failed to set up game");
+    let (winning_call, win) = game
         .play()
         .last()
         .map(|(winning_call, winning_boards)| {
@@ -276,7 +539,149 @@ fn part2(input: &Input) -> u32 {
         })
         .expect("Puzzle produced no winner for any bingo boards");
 
-    calculate_score(&winning_board, winning_call.into())
+    (
+        calculate_score(&win.board, winning_call.into()),
+        win.pattern_name,
+    )
+}
+
+/// Play a complete game, and report how every board finished, in the order they appear in the input
+fn finishing_report(input: &Input, rules: &[WinRule]) -> Vec<BoardReport> {
+    let mut reports = (0..input.boards.len())
+        .map(|board| BoardReport {
+            board,
+            position: None,
+            winning_call: None,
+            turn: None,
+            score: None,
+            pattern: None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut game = BingoGame::new(input.clone(), rules).expect("Failed to set up game");
+    let mut num_finished = 0;
+    for (winning_call, winning_boards) in game.play() {
+        let position = num_finished + 1;
+        num_finished += winning_boards.len();
+        for win in winning_boards {
+            let report = &mut reports[win.board_idx];
+            report.position = Some(position);
+            report.winning_call = Some(winning_call);
+            report.turn = Some(win.turn);
+            report.score = Some(calculate_score(&win.board, winning_call.into()));
+            report.pattern = Some(win.pattern_name);
+        }
+    }
+
+    reports
+}
+
+/// Play `num_games` games, each with the calls shuffled by a random number generator seeded with `seed`, and tally
+/// how each board fared across all of them
+fn simulate_shuffled_games(
+    input: &Input,
+    rules: &[WinRule],
+    num_games: u32,
+    seed: u64,
+) -> Vec<SimulationStats> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut stats = vec![SimulationStats::default(); input.boards.len()];
+    let mut shuffled_input = input.clone();
+    for _ in 0..num_games {
+        shuffled_input.calls.shuffle(&mut rng);
+        let reports = finishing_report(&shuffled_input, rules);
+        let last_position = reports.iter().filter_map(|report| report.position).max();
+        for (board_stats, report) in stats.iter_mut().zip(reports) {
+            let (Some(position), Some(turn)) = (report.position, report.turn) else {
+                continue;
+            };
+
+            board_stats.wins += 1;
+            board_stats.total_winning_turns += turn;
+            if position == 1 {
+                board_stats.first_wins += 1;
+            }
+            if Some(position) == last_position {
+                board_stats.last_wins += 1;
+            }
+        }
+    }
+
+    stats
+}
+
+/// Print the win probabilities and expected winning turn of every board over a simulation of `num_games` games
+fn print_simulation_stats(stats: &[SimulationStats], num_games: u32) {
+    let num_games = f64::from(num_games);
+    println!("board  P(first)  P(last)  P(win)  E[turn]");
+    for (board, board_stats) in stats.iter().enumerate() {
+        let expected_turn = if board_stats.wins == 0 {
+            "-".to_string()
+        } else {
+            // This could lose precision for an absurd number of turns, but that's fine for an estimate
+            #[allow(clippy::cast_precision_loss)]
+            let mean_turn = board_stats.total_winning_turns as f64 / f64::from(board_stats.wins);
+            format!("{mean_turn:.2}")
+        };
+
+        println!(
+            "{board:>5}  {:>8.4}  {:>7.4}  {:>6.4}  {expected_turn:>7}",
+            f64::from(board_stats.first_wins) / num_games,
+            f64::from(board_stats.last_wins) / num_games,
+            f64::from(board_stats.wins) / num_games,
+        );
+    }
+}
+
+/// Write the given board reports in the given format
+fn write_finishing_report<W: io::Write>(writer: W, reports: &[BoardReport], format: ReportFormat) {
+    match format {
+        ReportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(writer);
+            for report in reports {
+                writer.serialize(report).expect("Failed to write report");
+            }
+
+            writer.flush().expect("Failed to write report");
+        }
+        ReportFormat::Json => {
+            let mut writer = writer;
+            serde_json::to_writer_pretty(&mut writer, reports).expect("Failed to write report");
+            writeln!(writer).expect("Failed to write report");
+        }
+    }
+}
+
+fn parse_custom_pattern(input_chunk: &str) -> IResult<&str, CustomPattern> {
+    let (remaining, (name, grid)) = separated_pair(
+        not_line_ending,
+        char('\n'),
+        separated_list1(
+            char('\n'),
+            many1(alt((value(true, char('X')), value(false, char('.'))))),
+        ),
+    )(input_chunk)?;
+
+    // If every row isn't the same length, this pattern is not parsable.
+    if grid.iter().any(|row| row.len() != grid[0].len()) {
+        return fail(input_chunk);
+    }
+
+    let pattern = CustomPattern {
+        name: name.trim().to_string(),
+        grid,
+    };
+
+    Ok((remaining, pattern))
+}
+
+/// Parse a pattern file, which consists of blank-line separated patterns. Each pattern is a name on its own line,
+/// followed by a grid where 'X' marks a tile in the pattern and '.' marks a tile that is not.
+fn parse_pattern_file(input: &str) -> IResult<&str, Vec<CustomPattern>> {
+    terminated(
+        separated_list1(tag("\n\n"), parse_custom_pattern),
+        tuple((many0(tag("\n")), eof)),
+    )(input)
 }
 
 fn parse_input(input: &str) -> IResult<&str, Input> {
@@ -289,16 +694,110 @@ fn parse_input(input: &str) -> IResult<&str, Input> {
         tuple((many0(tag("\n")), eof)),
     )(input)?;
 
+    // All boards in a game must be the same size, which we take from the first board
+    let dimensions = boards[0].dimensions();
+    if boards.iter().any(|board| board.dimensions() != dimensions) {
+        return fail(input);
+    }
+
     let input = Input { calls, boards };
 
     Ok(("", input))
 }
 
 fn main() {
-    let input_file_name = env::args().nth(1).expect("No input filename specified");
+    let mut args = env::args().skip(1);
+    let input_file_name = args.next().expect("No input filename specified");
     let input = fs::read_to_string(input_file_name).expect("Could not open input file");
     let (_, parsed_input) = parse_input(&input).expect("Failed to parse input");
 
-    println!("Part 1: {}", part1(&parsed_input));
-    println!("Part 2: {}", part2(&parsed_input));
+    let mut rules = vec![];
+    let mut report = None;
+    let mut num_simulated_games = None;
+    let mut seed = 0;
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--simulate" => {
+                num_simulated_games = Some(
+                    args.next()
+                        .and_then(|n| n.parse().ok())
+                        .filter(|&n| n > 0)
+                        .expect("Number of games to simulate must be a positive integer"),
+                );
+            }
+            "--seed" => {
+                seed = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .expect("Seed must be an unsigned integer");
+            }
+            "--report" => {
+                let format = match args.next().as_deref() {
+                    Some("csv") => ReportFormat::Csv,
+                    Some("json") => ReportFormat::Json,
+                    _ => panic!("Report format must be one of 'csv' or 'json'"),
+                };
+                let path = args.next().expect("No report output filename specified");
+                report = Some((format, path));
+            }
+            "--rules" => {
+                let rule_names = args.next().expect("No rules specified");
+                for rule_name in rule_names.split(',') {
+                    rules.push(WinRule::try_from(rule_name).expect("Invalid rule"));
+                }
+            }
+            "--patterns" => {
+                let pattern_file_name = args.next().expect("No pattern filename specified");
+                let pattern_input =
+                    fs::read_to_string(pattern_file_name).expect("Could not open pattern file");
+                let (_, patterns) =
+                    parse_pattern_file(&pattern_input).expect("Failed to parse pattern file");
+                rules.push(WinRule::Custom(patterns));
+            }
+            _ => panic!("Unknown argument '{flag}'"),
+        }
+    }
+
+    let has_custom_rules = !rules.is_empty();
+    if rules.is_empty() {
+        rules.push(WinRule::Lines);
+    }
+
+    let (part1_score, part1_pattern) = part1(&parsed_input, &rules);
+    let (part2_score, part2_pattern) = part2(&parsed_input, &rules);
+    println!("Part 1: {part1_score}");
+    println!("Part 2: {part2_score}");
+
+    // The winning patterns are only interesting if there's more than the standard way to win
+    if has_custom_rules {
+        println!("Part 1 won by {part1_pattern}");
+        println!("Part 2 won by {part2_pattern}");
+    }
+
+    if let Some((format, path)) = report {
+        let output_file = File::create(path).expect("Could not create report file");
+        write_finishing_report(
+            output_file,
+            &finishing_report(&parsed_input, &rules),
+            format,
+        );
+    }
+
+    if let Some(num_games) = num_simulated_games {
+        let stats = simulate_shuffled_games(&parsed_input, &rules, num_games, seed);
+        print_simulation_stats(&stats, num_games);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_custom_pattern_is_rejected() {
+        let (_, patterns) = parse_pattern_file("Nothing\n...\n...\n...\n").unwrap();
+        let result = patterns[0].to_win_pattern((3, 3));
+
+        assert!(matches!(result, Err(Error::EmptyPattern(name)) if name == "Nothing"));
+    }
 }
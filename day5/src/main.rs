@@ -1,46 +1,76 @@
 #![warn(clippy::all, clippy::pedantic)]
 use std::cmp;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
 use nom::{
-    bytes::complete::{tag, take_while1},
-    character::complete::char,
-    combinator::eof,
-    combinator::map_res,
-    sequence::{separated_pair, terminated},
+    bytes::complete::tag,
+    character::complete::{char, digit1},
+    combinator::{eof, map_res, opt, recognize},
+    sequence::{pair, separated_pair, terminated},
     IResult,
 };
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 enum Error {
-    #[error("The coordinates {0:?} and {0:?} are not in line with the given strategy {:0?}")]
+    #[error("The coordinates {0:?} and {1:?} are not in line with the given strategy {2:?}")]
     InvalidDirection(Coordinate, Coordinate, Strategy),
+    #[error("Overlaps cannot be counted analytically with the strategy {0:?}")]
+    UnsupportedStrategy(Strategy),
+    #[error("The coordinate {0:?} is too large to count overlaps analytically")]
+    CoordinateOutOfRange(Coordinate),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct Coordinate(u32, u32);
+struct Coordinate(i64, i64);
 
 #[derive(Debug, Clone, Copy)]
 enum Strategy {
     OrthogonalOnly,
     OrthogonalAnd45Degrees,
+    AnySlope(Rasterization),
+}
+
+/// How the number of overlapping points is counted
+#[derive(Debug, Clone, Copy)]
+enum CountingMethod {
+    /// Count the lines that pass through every point on every line
+    PointMap,
+    /// Count the overlaps between each pair of lines, without visiting each point
+    Analytic,
+    /// Use both methods, and ensure they agree
+    CrossCheck,
+}
+
+/// How a line of any slope is converted to points on the grid
+#[derive(Debug, Clone, Copy)]
+enum Rasterization {
+    /// Only the lattice points that lie exactly on the line
+    Exact,
+    /// Every point that Bresenham's line algorithm would draw, which is the closest approximation of the line with
+    /// one point per step along its major axis
+    Bresenham,
+}
+
+/// The format to render a heatmap of overlaps in
+#[derive(Debug, Clone, Copy)]
+enum HeatmapFormat {
+    Ascii,
+    Pgm,
 }
 
 impl Strategy {
     fn points_follow_strategy(self, a: Coordinate, b: Coordinate) -> bool {
-        let x_range = order_pair(a.0, b.0);
-        let y_range = order_pair(a.1, b.1);
-
-        let rise = y_range.1 - y_range.0;
-        let run = x_range.1 - x_range.0;
+        let rise = a.1.abs_diff(b.1);
+        let run = a.0.abs_diff(b.0);
 
         match self {
             Self::OrthogonalOnly => run == 0 || rise == 0,
             Self::OrthogonalAnd45Degrees => run == 0 || rise == 0 || run == rise,
+            Self::AnySlope(_) => true,
         }
     }
 }
@@ -55,25 +85,103 @@ impl Coordinate {
         self,
         other: Coordinate,
         strategy: Strategy,
-    ) -> Result<impl Iterator<Item = Coordinate>, Error> {
+    ) -> Result<Box<dyn Iterator<Item = Coordinate>>, Error> {
         if !strategy.points_follow_strategy(self, other) {
             return Err(Error::InvalidDirection(self, other, strategy));
         }
 
-        let x_range = order_pair(self.0, other.0);
-        let y_range = order_pair(self.1, other.1);
-        // Calculate the distance between points using the "max norm"
-        // this is like the manhattan distance, but diagonals are 1
-        // (I'm effectively using it as an integral euclidian distance given the context)
-        let travel_distance = cmp::max(x_range.1 - x_range.0, y_range.1 - y_range.0);
+        if let Strategy::AnySlope(Rasterization::Bresenham) = strategy {
+            return Ok(Box::new(BresenhamIter::new(self, other)));
+        }
+
+        let run = self.0.abs_diff(other.0);
+        let rise = self.1.abs_diff(other.1);
+
+        // Lattice points on the line are evenly spaced, and there is one for each step of (run/g, rise/g), where g
+        // is the gcd of the run and rise. For orthogonal and 45 degree lines, this is a step of one in each direction
+        // that moves at all.
+        let num_steps = gcd(run, rise);
+        let x_step = run.checked_div(num_steps).unwrap_or(0);
+        let y_step = rise.checked_div(num_steps).unwrap_or(0);
 
-        let iter = (0..=travel_distance).map(move |n| {
-            let end_x = add_to_component_directionally(self.0, other.0, n);
-            let end_y = add_to_component_directionally(self.1, other.1, n);
+        let iter = (0..=num_steps).map(move |n| {
+            let end_x = add_to_component_directionally(self.0, other.0, n * x_step);
+            let end_y = add_to_component_directionally(self.1, other.1, n * y_step);
 
             Coordinate(end_x, end_y)
         });
-        Ok(iter)
+        Ok(Box::new(iter))
+    }
+}
+
+/// An iterator over the points of a line, as drawn by Bresenham's line algorithm.
+///
+/// The distance between two i64s may not fit in an i64, so all arithmetic is done with i128s.
+struct BresenhamIter {
+    current: (i128, i128),
+    end: (i128, i128),
+    delta: (i128, i128),
+    step: (i128, i128),
+    error: i128,
+    done: bool,
+}
+
+impl BresenhamIter {
+    fn new(start: Coordinate, end: Coordinate) -> Self {
+        let current = (i128::from(start.0), i128::from(start.1));
+        let end = (i128::from(end.0), i128::from(end.1));
+        let delta = ((end.0 - current.0).abs(), -(end.1 - current.1).abs());
+        let step = ((end.0 - current.0).signum(), (end.1 - current.1).signum());
+
+        Self {
+            current,
+            end,
+            delta,
+            step,
+            error: delta.0 + delta.1,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for BresenhamIter {
+    type Item = Coordinate;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        // All points lie between the two (i64) endpoints, so they must fit in an i64
+        let point = Coordinate(
+            i64::try_from(self.current.0).expect("line point out of range"),
+            i64::try_from(self.current.1).expect("line point out of range"),
+        );
+
+        if self.current == self.end {
+            self.done = true;
+            return Some(point);
+        }
+
+        let doubled_error = 2 * self.error;
+        if doubled_error >= self.delta.1 {
+            self.error += self.delta.1;
+            self.current.0 += self.step.0;
+        }
+        if doubled_error <= self.delta.0 {
+            self.error += self.delta.0;
+            self.current.1 += self.step.1;
+        }
+
+        Some(point)
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
     }
 }
 
@@ -86,12 +194,15 @@ fn order_pair<T: PartialOrd>(a: T, b: T) -> (T, T) {
 }
 
 /// Add some number, n, to the start component of a vector, in the direction of its ending point.
-fn add_to_component_directionally(start: u32, end: u32, n: u32) -> u32 {
-    match start.cmp(&end) {
-        cmp::Ordering::Equal => start,
-        cmp::Ordering::Greater => start - n,
-        cmp::Ordering::Less => start + n,
-    }
+/// n must not be larger than the distance between the two.
+fn add_to_component_directionally(start: i64, end: i64, n: u64) -> i64 {
+    let moved = match start.cmp(&end) {
+        cmp::Ordering::Equal => Some(start),
+        cmp::Ordering::Greater => start.checked_sub_unsigned(n),
+        cmp::Ordering::Less => start.checked_add_unsigned(n),
+    };
+
+    moved.expect("moved past the end of the vector")
 }
 
 /// Build a map of the number of intersections between lines bounded (inclusively) by each element
@@ -120,22 +231,462 @@ fn build_intersection_count_map(
     Ok(counts)
 }
 
-fn part1(coordinate_pairs: &[(Coordinate, Coordinate)]) -> usize {
-    let map = build_intersection_count_map(coordinate_pairs, Strategy::OrthogonalOnly)
-        .expect("Failed to build coordinate map from input");
+/// A line segment, as the lattice points `start + k * step` for `0 <= k <= num_steps`. `step` is the smallest
+/// integral step along the line, and is zero if the segment is a single point.
+#[derive(Debug, Clone, Copy)]
+struct LatticeSegment {
+    start: (i128, i128),
+    step: (i128, i128),
+    num_steps: i128,
+}
 
-    map.values().filter(|&&n| n >= 2).count()
+/// The infinite line that a set of collinear segments lie on. `direction` is a canonical form of the segments' step
+/// (pointing to the right, or straight up), and `offset` is the cross product of the direction with any point on the
+/// line, which is the same for all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct LineKey {
+    direction: (i128, i128),
+    offset: i128,
 }
 
-fn part2(coordinate_pairs: &[(Coordinate, Coordinate)]) -> usize {
-    let map = build_intersection_count_map(coordinate_pairs, Strategy::OrthogonalAnd45Degrees)
-        .expect("Failed to build coordinate map from input");
+fn cross(a: (i128, i128), b: (i128, i128)) -> i128 {
+    a.0 * b.1 - a.1 * b.0
+}
 
-    map.values().filter(|&&n| n >= 2).count()
+fn dot(a: (i128, i128), b: (i128, i128)) -> i128 {
+    a.0 * b.0 + a.1 * b.1
 }
 
-fn parse_number(s: &str) -> IResult<&str, u32> {
-    map_res(take_while1(|c: char| c.is_ascii_digit()), str::parse)(s)
+impl LatticeSegment {
+    /// The largest magnitude a coordinate can have. This ensures that the products of any two points or steps
+    /// (and the sums of those products) cannot overflow an i128.
+    const MAX_COMPONENT: u64 = 1 << 61;
+
+    /// Ensure the given coordinate is small enough to be part of a segment
+    fn validate_coordinate(coordinate: Coordinate) -> Result<(), Error> {
+        if coordinate.0.unsigned_abs() >= Self::MAX_COMPONENT
+            || coordinate.1.unsigned_abs() >= Self::MAX_COMPONENT
+        {
+            return Err(Error::CoordinateOutOfRange(coordinate));
+        }
+
+        Ok(())
+    }
+
+    fn new(start_coordinate: Coordinate, end_coordinate: Coordinate) -> Result<Self, Error> {
+        Self::validate_coordinate(start_coordinate)?;
+        Self::validate_coordinate(end_coordinate)?;
+
+        let start = (
+            i128::from(start_coordinate.0),
+            i128::from(start_coordinate.1),
+        );
+        let num_steps = i128::from(gcd(
+            start_coordinate.0.abs_diff(end_coordinate.0),
+            start_coordinate.1.abs_diff(end_coordinate.1),
+        ));
+        let delta = (
+            i128::from(end_coordinate.0) - start.0,
+            i128::from(end_coordinate.1) - start.1,
+        );
+        let step = if num_steps == 0 {
+            (0, 0)
+        } else {
+            (delta.0 / num_steps, delta.1 / num_steps)
+        };
+
+        Ok(Self {
+            start,
+            step,
+            num_steps,
+        })
+    }
+
+    fn point_at(&self, k: i128) -> (i128, i128) {
+        (
+            self.start.0 + k * self.step.0,
+            self.start.1 + k * self.step.1,
+        )
+    }
+
+    /// Get the k such that `point_at(k)` is the given point, if the point is a lattice point on this segment
+    fn position_of(&self, point: (i128, i128)) -> Option<i128> {
+        let offset = (point.0 - self.start.0, point.1 - self.start.1);
+        if self.num_steps == 0 {
+            return (offset == (0, 0)).then_some(0);
+        }
+
+        if cross(offset, self.step) != 0 {
+            return None;
+        }
+
+        let k = self.position_of_on_line(point);
+        (0..=self.num_steps).contains(&k).then_some(k)
+    }
+
+    /// Get the k such that `point_at(k)` is the given point, which must be a lattice point on the infinite line this
+    /// segment lies on (and this segment must not be a single point)
+    fn position_of_on_line(&self, point: (i128, i128)) -> i128 {
+        // Any lattice point on the line is an integral number of steps away, as the step is the smallest one possible
+        let offset = (point.0 - self.start.0, point.1 - self.start.1);
+        dot(offset, self.step) / dot(self.step, self.step)
+    }
+
+    fn line_key(&self) -> LineKey {
+        let direction = if self.step.0 < 0 || (self.step.0 == 0 && self.step.1 < 0) {
+            (-self.step.0, -self.step.1)
+        } else {
+            self.step
+        };
+
+        LineKey {
+            direction,
+            offset: cross(direction, self.start),
+        }
+    }
+}
+
+/// One end of a run of overlapping points along a line, along with its dot product with the line's direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct RunEnd {
+    projection: i128,
+    point: (i128, i128),
+}
+
+impl RunEnd {
+    fn new(key: &LineKey, point: (i128, i128)) -> Self {
+        Self {
+            projection: dot(key.direction, point),
+            point,
+        }
+    }
+}
+
+/// The points shared by two segments
+enum SegmentOverlap {
+    None,
+    Point((i128, i128)),
+    /// A run of collinear points, between the two given points (inclusive)
+    Run((i128, i128), (i128, i128)),
+}
+
+/// Find all of the lattice points shared by the two segments
+fn find_overlap(a: &LatticeSegment, b: &LatticeSegment) -> SegmentOverlap {
+    if a.num_steps == 0 {
+        return b
+            .position_of(a.start)
+            .map_or(SegmentOverlap::None, |_| SegmentOverlap::Point(a.start));
+    } else if b.num_steps == 0 {
+        return a
+            .position_of(b.start)
+            .map_or(SegmentOverlap::None, |_| SegmentOverlap::Point(b.start));
+    }
+
+    let start_offset = (b.start.0 - a.start.0, b.start.1 - a.start.1);
+    let step_cross = cross(a.step, b.step);
+    if step_cross == 0 {
+        // The segments are parallel, so they only overlap if they are on the same line
+        if cross(start_offset, a.step) != 0 {
+            return SegmentOverlap::None;
+        }
+
+        let b_start_pos = a.position_of_on_line(b.start);
+        let b_end_pos = a.position_of_on_line(b.point_at(b.num_steps));
+        let (b_min, b_max) = order_pair(b_start_pos, b_end_pos);
+        let (low, high) = (b_min.max(0), b_max.min(a.num_steps));
+
+        return if low > high {
+            SegmentOverlap::None
+        } else {
+            SegmentOverlap::Run(a.point_at(low), a.point_at(high))
+        };
+    }
+
+    // Solve a.start + t * a.step = b.start + u * b.step; the point is only on both segments' lattices if both t and u
+    // are integral and in bounds
+    let t_numerator = cross(start_offset, b.step);
+    let u_numerator = cross(start_offset, a.step);
+    if t_numerator % step_cross != 0 || u_numerator % step_cross != 0 {
+        return SegmentOverlap::None;
+    }
+
+    let t = t_numerator / step_cross;
+    let u = u_numerator / step_cross;
+    if (0..=a.num_steps).contains(&t) && (0..=b.num_steps).contains(&u) {
+        SegmentOverlap::Point(a.point_at(t))
+    } else {
+        SegmentOverlap::None
+    }
+}
+
+/// Count the number of points at which at least two lines overlap, by finding the overlap of every pair of lines.
+/// Unlike [`build_intersection_count_map`], this never visits every point on each line, so its runtime only depends
+/// on the number of lines, not their length.
+///
+/// # Errors
+/// Only strategies that consider the exact lattice points on a line can be counted this way. If any other is given,
+/// [`Error::UnsupportedStrategy`] is returned.
+fn count_overlapping_points_analytically(
+    coordinate_pairs: &[(Coordinate, Coordinate)],
+    strategy: Strategy,
+) -> Result<usize, Error> {
+    if let Strategy::AnySlope(Rasterization::Bresenham) = strategy {
+        return Err(Error::UnsupportedStrategy(strategy));
+    }
+
+    let segments = coordinate_pairs
+        .iter()
+        .filter(|(start, end)| strategy.points_follow_strategy(*start, *end))
+        .map(|&(start, end)| LatticeSegment::new(start, end))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Overlapping runs are stored by the line they are on, as ranges of the dot product of the line's direction with
+    // the points at either end. Every lattice point along a line has a dot product that is a multiple of the
+    // direction's squared length apart, so these can be counted without visiting each point.
+    let mut runs = HashMap::<LineKey, Vec<(RunEnd, RunEnd)>>::new();
+    let mut points = HashSet::new();
+    for (i, a) in segments.iter().enumerate() {
+        for b in &segments[i + 1..] {
+            match find_overlap(a, b) {
+                SegmentOverlap::None => (),
+                SegmentOverlap::Point(point) => {
+                    points.insert(point);
+                }
+                SegmentOverlap::Run(run_start, run_end) => {
+                    let key = a.line_key();
+                    let range =
+                        order_pair(RunEnd::new(&key, run_start), RunEnd::new(&key, run_end));
+                    runs.entry(key).or_default().push(range);
+                }
+            }
+        }
+    }
+
+    let mut count = 0;
+    for (key, ranges) in &mut runs {
+        *ranges = merge_ranges(ranges);
+        let spacing = dot(key.direction, key.direction);
+        count += ranges
+            .iter()
+            .map(|(low, high)| {
+                usize::try_from((high.projection - low.projection) / spacing + 1)
+                    .expect("run length must fit in a usize")
+            })
+            .sum::<usize>();
+    }
+
+    let directions = runs.keys().map(|key| key.direction).collect::<HashSet<_>>();
+    let is_in_run = |point: (i128, i128)| {
+        directions.iter().any(|&direction| {
+            let key = LineKey {
+                direction,
+                offset: cross(direction, point),
+            };
+            let projection = dot(direction, point);
+
+            runs.get(&key).is_some_and(|ranges| {
+                let idx = ranges.partition_point(|(_, high)| high.projection < projection);
+                ranges
+                    .get(idx)
+                    .is_some_and(|(low, _)| low.projection <= projection)
+            })
+        })
+    };
+
+    count += points
+        .into_iter()
+        .filter(|&point| !is_in_run(point))
+        .count();
+
+    // Runs on different lines can cross each other, in which case the point they cross at has been counted once for
+    // each of them
+    let run_segments = runs
+        .iter()
+        .flat_map(|(key, ranges)| {
+            let spacing = dot(key.direction, key.direction);
+            ranges.iter().map(move |(low, high)| {
+                let segment = LatticeSegment {
+                    start: low.point,
+                    step: key.direction,
+                    num_steps: (high.projection - low.projection) / spacing,
+                };
+
+                (*key, segment)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut crossing_lines = HashMap::<(i128, i128), HashSet<LineKey>>::new();
+    for (i, (a_key, a)) in run_segments.iter().enumerate() {
+        for (b_key, b) in &run_segments[i + 1..] {
+            if a_key == b_key {
+                continue;
+            }
+
+            if let SegmentOverlap::Point(point) = find_overlap(a, b) {
+                let lines = crossing_lines.entry(point).or_default();
+                lines.insert(*a_key);
+                lines.insert(*b_key);
+            }
+        }
+    }
+
+    count -= crossing_lines
+        .values()
+        .map(|lines| lines.len() - 1)
+        .sum::<usize>();
+
+    Ok(count)
+}
+
+/// Merge all overlapping ranges, returning them in sorted order
+fn merge_ranges(ranges: &[(RunEnd, RunEnd)]) -> Vec<(RunEnd, RunEnd)> {
+    let mut sorted_ranges = ranges.to_vec();
+    sorted_ranges.sort_unstable();
+
+    let mut merged: Vec<(RunEnd, RunEnd)> = Vec::with_capacity(sorted_ranges.len());
+    for (low, high) in sorted_ranges {
+        match merged.last_mut() {
+            Some(last) if low.projection <= last.1.projection => last.1 = last.1.max(high),
+            _ => merged.push((low, high)),
+        }
+    }
+
+    merged
+}
+
+/// Count the number of points in an intersection count map where at least `min_count` lines overlap
+fn count_points_with_at_least(map: &HashMap<Coordinate, u32>, min_count: u32) -> usize {
+    map.values().filter(|&&n| n >= min_count).count()
+}
+
+/// Get the `n` points in an intersection count map with the most overlapping lines, from most to least. Ties are
+/// broken by position, top to bottom and then left to right.
+fn hottest_points(map: &HashMap<Coordinate, u32>, n: usize) -> Vec<(Coordinate, u32)> {
+    let mut points = map
+        .iter()
+        .map(|(&coordinate, &count)| (coordinate, count))
+        .collect::<Vec<_>>();
+    points.sort_unstable_by_key(|&(coordinate, count)| {
+        (cmp::Reverse(count), coordinate.1, coordinate.0)
+    });
+    points.truncate(n);
+
+    points
+}
+
+/// Get the inclusive bounds of all points in an intersection count map, as the top left and bottom right corners
+fn map_bounds(map: &HashMap<Coordinate, u32>) -> Option<(Coordinate, Coordinate)> {
+    let min_x = map.keys().map(|coordinate| coordinate.0).min()?;
+    let max_x = map.keys().map(|coordinate| coordinate.0).max()?;
+    let min_y = map.keys().map(|coordinate| coordinate.1).min()?;
+    let max_y = map.keys().map(|coordinate| coordinate.1).max()?;
+
+    Some((Coordinate(min_x, min_y), Coordinate(max_x, max_y)))
+}
+
+/// Render an intersection count map in the same style as the puzzle: '.' where there are no lines, the number of
+/// lines where there are fewer than ten, and '*' otherwise
+fn render_ascii_heatmap(map: &HashMap<Coordinate, u32>) -> String {
+    let Some((top_left, bottom_right)) = map_bounds(map) else {
+        return String::new();
+    };
+
+    let mut rendered = String::new();
+    for y in top_left.1..=bottom_right.1 {
+        for x in top_left.0..=bottom_right.0 {
+            let tile = match map.get(&Coordinate(x, y)) {
+                None => '.',
+                Some(&count) => char::from_digit(count, 10).unwrap_or('*'),
+            };
+
+            rendered.push(tile);
+        }
+
+        rendered.push('\n');
+    }
+
+    rendered
+}
+
+/// Render an intersection count map as a plain (P2) PGM image, where brighter pixels have more overlapping lines
+fn render_pgm_heatmap(map: &HashMap<Coordinate, u32>) -> String {
+    let Some((top_left, bottom_right)) = map_bounds(map) else {
+        return "P2\n0 0\n1\n".to_string();
+    };
+
+    let max_count = map.values().copied().max().unwrap_or(1);
+    let mut rendered = format!(
+        "P2\n{} {}\n{max_count}\n",
+        top_left.0.abs_diff(bottom_right.0) + 1,
+        top_left.1.abs_diff(bottom_right.1) + 1,
+    );
+    for y in top_left.1..=bottom_right.1 {
+        let row = (top_left.0..=bottom_right.0)
+            .map(|x| map.get(&Coordinate(x, y)).unwrap_or(&0).to_string())
+            .collect::<Vec<_>>();
+
+        rendered.push_str(&row.join(" "));
+        rendered.push('\n');
+    }
+
+    rendered
+}
+
+/// Count the number of points at which at least two lines overlap
+fn count_overlapping_points(
+    coordinate_pairs: &[(Coordinate, Coordinate)],
+    strategy: Strategy,
+    method: CountingMethod,
+) -> Result<usize, Error> {
+    let count_with_map = || {
+        let map = build_intersection_count_map(coordinate_pairs, strategy)?;
+
+        Ok(count_points_with_at_least(&map, 2))
+    };
+    let count_analytically = || count_overlapping_points_analytically(coordinate_pairs, strategy);
+
+    match method {
+        CountingMethod::PointMap => count_with_map(),
+        CountingMethod::Analytic => count_analytically(),
+        CountingMethod::CrossCheck => {
+            let count = count_with_map()?;
+            assert_eq!(
+                count,
+                count_analytically()?,
+                "Analytic count does not match the count from the point map"
+            );
+
+            Ok(count)
+        }
+    }
+}
+
+/// Ensure every coordinate can be used to count overlaps analytically, so that any problem is reported before any
+/// counting starts
+fn validate_coordinates(coordinate_pairs: &[(Coordinate, Coordinate)]) -> Result<(), Error> {
+    coordinate_pairs
+        .iter()
+        .flat_map(|&(start, end)| [start, end])
+        .try_for_each(LatticeSegment::validate_coordinate)
+}
+
+fn part1(
+    coordinate_pairs: &[(Coordinate, Coordinate)],
+    method: CountingMethod,
+) -> Result<usize, Error> {
+    count_overlapping_points(coordinate_pairs, Strategy::OrthogonalOnly, method)
+}
+
+fn part2(
+    coordinate_pairs: &[(Coordinate, Coordinate)],
+    method: CountingMethod,
+) -> Result<usize, Error> {
+    count_overlapping_points(coordinate_pairs, Strategy::OrthogonalAnd45Degrees, method)
+}
+
+fn parse_number(s: &str) -> IResult<&str, i64> {
+    map_res(recognize(pair(opt(char('-')), digit1)), str::parse)(s)
 }
 
 fn parse_coordinate(s: &str) -> IResult<&str, Coordinate> {
@@ -164,6 +715,162 @@ fn main() {
         })
         .collect::<Vec<_>>();
 
-    println!("Part 1: {}", part1(&input_coordinates));
-    println!("Part 2: {}", part2(&input_coordinates));
+    let mut any_slope_rasterization = None;
+    let mut method = CountingMethod::PointMap;
+    let mut heatmap_format = None;
+    let mut min_overlaps = None;
+    let mut num_hottest_points = None;
+    let mut args = env::args().skip(2);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--exact" => any_slope_rasterization = Some(Rasterization::Exact),
+            "--bresenham" => any_slope_rasterization = Some(Rasterization::Bresenham),
+            "--analytic" => method = CountingMethod::Analytic,
+            "--cross-check" => method = CountingMethod::CrossCheck,
+            "--heatmap" => {
+                heatmap_format = match args.next().as_deref() {
+                    Some("ascii") => Some(HeatmapFormat::Ascii),
+                    Some("pgm") => Some(HeatmapFormat::Pgm),
+                    _ => panic!("Heatmap format must be one of 'ascii' or 'pgm'"),
+                };
+            }
+            "--at-least" => {
+                min_overlaps = Some(
+                    args.next()
+                        .and_then(|n| n.parse::<u32>().ok())
+                        .expect("Minimum number of overlaps must be a non-negative integer"),
+                );
+            }
+            "--hottest" => {
+                num_hottest_points = Some(
+                    args.next()
+                        .and_then(|n| n.parse::<usize>().ok())
+                        .expect("Number of hottest points must be a non-negative integer"),
+                );
+            }
+            _ => panic!("Unknown argument '{arg}'"),
+        }
+    }
+
+    // Bresenham lines aren't lattice lines, so their overlaps can only be counted point by point
+    assert!(
+        !matches!(any_slope_rasterization, Some(Rasterization::Bresenham))
+            || matches!(method, CountingMethod::PointMap),
+        "--bresenham cannot be combined with --analytic or --cross-check"
+    );
+
+    if !matches!(method, CountingMethod::PointMap) {
+        validate_coordinates(&input_coordinates)
+            .unwrap_or_else(|err| panic!("Invalid input: {err}"));
+    }
+
+    let part1_count = part1(&input_coordinates, method).expect("Failed to count overlaps");
+    println!("Part 1: {part1_count}");
+    let part2_count = part2(&input_coordinates, method).expect("Failed to count overlaps");
+    println!("Part 2: {part2_count}");
+
+    if let Some(rasterization) = any_slope_rasterization {
+        let count = count_overlapping_points(
+            &input_coordinates,
+            Strategy::AnySlope(rasterization),
+            method,
+        )
+        .expect("Failed to count overlaps");
+        println!("Any slope: {count}");
+    }
+
+    if heatmap_format.is_none() && min_overlaps.is_none() && num_hottest_points.is_none() {
+        return;
+    }
+
+    // The map's queries use the lines from part 2, unless lines of any slope were asked for
+    let strategy =
+        any_slope_rasterization.map_or(Strategy::OrthogonalAnd45Degrees, Strategy::AnySlope);
+    let map = build_intersection_count_map(&input_coordinates, strategy)
+        .expect("Failed to build coordinate map from input");
+
+    if let Some(min_count) = min_overlaps {
+        println!(
+            "Points with at least {min_count} overlaps: {}",
+            count_points_with_at_least(&map, min_count)
+        );
+    }
+
+    if let Some(n) = num_hottest_points {
+        println!("Hottest points:");
+        for (Coordinate(x, y), count) in hottest_points(&map, n) {
+            println!("{x},{y}: {count}");
+        }
+    }
+
+    match heatmap_format {
+        None => (),
+        Some(HeatmapFormat::Ascii) => print!("{}", render_ascii_heatmap(&map)),
+        Some(HeatmapFormat::Pgm) => print!("{}", render_pgm_heatmap(&map)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "0,9 -> 5,9
+8,0 -> 0,8
+9,4 -> 3,4
+2,2 -> 2,1
+7,0 -> 7,4
+6,4 -> 2,0
+0,9 -> 2,9
+3,4 -> 1,4
+0,0 -> 8,8
+5,5 -> 8,2";
+
+    fn parse_example(extra_lines: &[&str]) -> Vec<(Coordinate, Coordinate)> {
+        EXAMPLE
+            .lines()
+            .chain(extra_lines.iter().copied())
+            .map(|line| parse_line(line).unwrap().1)
+            .collect()
+    }
+
+    #[test]
+    fn analytic_count_matches_point_map() {
+        // Collinear overlapping diagonals, a single point, and lines that only meet at points of other slopes
+        let coordinate_pairs = parse_example(&[
+            "1,1 -> 4,4",
+            "6,6 -> 6,6",
+            "0,0 -> 6,3",
+            "-4,-2 -> 2,1",
+            "9,0 -> 0,6",
+        ]);
+
+        for strategy in [
+            Strategy::OrthogonalOnly,
+            Strategy::OrthogonalAnd45Degrees,
+            Strategy::AnySlope(Rasterization::Exact),
+        ] {
+            let from_map =
+                count_overlapping_points(&coordinate_pairs, strategy, CountingMethod::PointMap)
+                    .unwrap();
+            let analytic =
+                count_overlapping_points(&coordinate_pairs, strategy, CountingMethod::Analytic)
+                    .unwrap();
+
+            assert_eq!(analytic, from_map, "{strategy:?}");
+        }
+    }
+
+    #[test]
+    fn analytic_count_solves_example() {
+        let coordinate_pairs = parse_example(&[]);
+
+        assert_eq!(
+            part1(&coordinate_pairs, CountingMethod::Analytic).unwrap(),
+            5
+        );
+        assert_eq!(
+            part2(&coordinate_pairs, CountingMethod::Analytic).unwrap(),
+            12
+        );
+    }
 }
@@ -1,36 +1,299 @@
 #![warn(clippy::all, clippy::pedantic)]
+use num_bigint::BigUint;
 use std::env;
-use std::fs;
-
-fn simulate(input: &[u8], num_days: u32) -> u64 {
-    // A "map" of each day value of the laternfishes, 0-8 (hence size 9).
-    let mut day_map = [0_u64; 9];
-
-    #[allow(clippy::cast_possible_truncation, clippy::naive_bytecount)]
-    day_map.iter_mut().enumerate().for_each(|(i, n)| {
-        // i can be safely cast because we know that i will not exceed 8
-        //
-        // The count can be safely cast because we know that it's going to be pretty small given the input size
-        // (at most 300 if every element is the same, at least on my input)
-        //
-        // We _could_ use the bytecount crate to satisfy clippy's naive_bytecount warning, but the actual input
-        // is like, 300 elements long so any performance loss due to not using it is going to be super neglibible
-        *n = input.iter().filter(|&&n| n == i as u8).count() as u64;
-    });
-
-    for _ in 0..num_days {
-        let to_add = day_map[0];
-        // Shift all elements down one
-        for i in 1..day_map.len() {
-            day_map[i - 1] = day_map[i];
+use std::fs::{self, File};
+use std::io;
+use thiserror::Error;
+
+/// The fixed point scale of the growth factors in the time series, giving six decimal places
+const GROWTH_SCALE: u32 = 1_000_000;
+
+#[derive(Debug, Error)]
+enum Error {
+    #[error("Timer value {timer} is too large for a life cycle with {num_timers} timer values")]
+    TimerOutOfRange { timer: u8, num_timers: usize },
+    #[error("Failed to write time series: {0}")]
+    TimeSeries(#[from] csv::Error),
+}
+
+/// The parameters of a species' life cycle
+#[derive(Debug, Clone, Copy)]
+struct LifeCycle {
+    /// The timer value a fish resets to after spawning a new fish
+    reset_timer: usize,
+    /// The timer value a newly spawned fish starts with
+    newborn_timer: usize,
+    /// The number of days a newly spawned fish waits before its timer starts to count down
+    maturation_delay: usize,
+}
+
+impl Default for LifeCycle {
+    /// The life cycle of a lanternfish, as given by the puzzle
+    fn default() -> Self {
+        Self {
+            reset_timer: 6,
+            newborn_timer: 8,
+            maturation_delay: 0,
+        }
+    }
+}
+
+impl LifeCycle {
+    /// The timer value a newly spawned fish is placed at. Waiting to mature is no different from having a longer
+    /// timer, so the delay is folded into it.
+    fn spawn_timer(&self) -> usize {
+        self.newborn_timer + self.maturation_delay
+    }
+
+    /// The number of distinct timer values a fish can have
+    fn num_timers(&self) -> usize {
+        self.reset_timer.max(self.spawn_timer()) + 1
+    }
+}
+
+/// A square matrix, stored as a list of rows
+type Matrix<T> = Vec<Vec<T>>;
+
+/// The arithmetic used to compute populations with a transition matrix
+trait Arithmetic {
+    type Value: Clone;
+
+    fn value_of(&self, n: u64) -> Self::Value;
+    fn add(&self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+    fn mul(&self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+
+    fn zero(&self) -> Self::Value {
+        self.value_of(0)
+    }
+
+    fn one(&self) -> Self::Value {
+        self.value_of(1)
+    }
+}
+
+/// Arbitrary precision arithmetic, which will never overflow (but may get very slow for very large populations)
+struct ExactArithmetic;
+
+/// Arithmetic modulo some number, which keeps values small regardless of how many days are simulated
+struct ModularArithmetic(u64);
+
+impl Arithmetic for ExactArithmetic {
+    type Value = BigUint;
+
+    fn value_of(&self, n: u64) -> Self::Value {
+        BigUint::from(n)
+    }
+
+    fn add(&self, a: &Self::Value, b: &Self::Value) -> Self::Value {
+        a + b
+    }
+
+    fn mul(&self, a: &Self::Value, b: &Self::Value) -> Self::Value {
+        a * b
+    }
+}
+
+impl Arithmetic for ModularArithmetic {
+    type Value = u64;
+
+    fn value_of(&self, n: u64) -> Self::Value {
+        n % self.0
+    }
+
+    fn add(&self, a: &Self::Value, b: &Self::Value) -> Self::Value {
+        self.reduce(u128::from(*a) + u128::from(*b))
+    }
+
+    fn mul(&self, a: &Self::Value, b: &Self::Value) -> Self::Value {
+        self.reduce(u128::from(*a) * u128::from(*b))
+    }
+}
+
+impl ModularArithmetic {
+    fn reduce(&self, n: u128) -> u64 {
+        // The modulus is a u64, so the remainder must fit in one
+        u64::try_from(n % u128::from(self.0)).expect("remainder must be smaller than the modulus")
+    }
+}
+
+/// Count the number of fish with each timer value
+fn count_timers(input: &[u8], life_cycle: &LifeCycle) -> Result<Vec<u64>, Error> {
+    let mut day_map = vec![0_u64; life_cycle.num_timers()];
+    for &timer in input {
+        let count = day_map
+            .get_mut(usize::from(timer))
+            .ok_or(Error::TimerOutOfRange {
+                timer,
+                num_timers: life_cycle.num_timers(),
+            })?;
+
+        *count += 1;
+    }
+
+    Ok(day_map)
+}
+
+/// Advance the given timer counts by a single day
+fn step(day_map: &mut [BigUint], life_cycle: &LifeCycle) {
+    let to_add = day_map[0].clone();
+    // Shift all elements down one
+    day_map.rotate_left(1);
+    *day_map.last_mut().expect("day map cannot be empty") = BigUint::default();
+
+    // Add the newly spawned elements (and the fish that spawned them)
+    day_map[life_cycle.reset_timer] += &to_add;
+    day_map[life_cycle.spawn_timer()] += &to_add;
+}
+
+/// Write the population for every day from day 0 up to and including `num_days` as CSV, with one row per day. Each
+/// row holds the total population, the growth factor since the previous day (truncated to six decimal places), and
+/// the number of fish with each timer value. Rows are written as the population is stepped forward, so only a single
+/// day is held in memory at a time.
+fn write_time_series<W: io::Write>(
+    writer: W,
+    input: &[u8],
+    life_cycle: &LifeCycle,
+    num_days: u32,
+) -> Result<(), Error> {
+    // The time series is used to see how the population grows over long periods, so the counts must be exact
+    let mut day_map = count_timers(input, life_cycle)?
+        .into_iter()
+        .map(BigUint::from)
+        .collect::<Vec<_>>();
+
+    let mut writer = csv::Writer::from_writer(writer);
+    let mut header = vec!["day".to_string(), "total".to_string(), "growth".to_string()];
+    header.extend((0..day_map.len()).map(|timer| format!("timer_{timer}")));
+    writer.write_record(&header)?;
+
+    let mut previous_total: Option<BigUint> = None;
+    for day in 0..=num_days {
+        if day > 0 {
+            step(&mut day_map, life_cycle);
         }
 
-        // Add the newly spawned elements (n=8 starts at 0)
-        day_map[6] += to_add;
-        day_map[8] = to_add;
+        let total = day_map.iter().sum::<BigUint>();
+        // The totals can be far too large for a float, so the ratio is computed in fixed point
+        let growth = previous_total
+            .filter(|previous| *previous > BigUint::default())
+            .map_or_else(String::new, |previous| {
+                let scaled = &total * GROWTH_SCALE / previous;
+                let fraction = u32::try_from(&scaled % GROWTH_SCALE)
+                    .expect("remainder must be smaller than the scale");
+
+                format!("{}.{fraction:06}", scaled / GROWTH_SCALE)
+            });
+
+        let mut record = vec![day.to_string(), total.to_string(), growth];
+        record.extend(day_map.iter().map(BigUint::to_string));
+        writer.write_record(&record)?;
+        previous_total = Some(total);
+    }
+
+    writer.flush().map_err(csv::Error::from)?;
+    Ok(())
+}
+
+fn matrix_multiply<A: Arithmetic>(
+    arithmetic: &A,
+    a: &Matrix<A::Value>,
+    b: &Matrix<A::Value>,
+) -> Matrix<A::Value> {
+    (0..a.len())
+        .map(|row| {
+            (0..b[0].len())
+                .map(|col| {
+                    (0..b.len()).fold(arithmetic.zero(), |total, i| {
+                        arithmetic.add(&total, &arithmetic.mul(&a[row][i], &b[i][col]))
+                    })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Raise a square matrix to the given power, by repeated squaring
+fn matrix_power<A: Arithmetic>(
+    arithmetic: &A,
+    matrix: &Matrix<A::Value>,
+    mut exponent: u64,
+) -> Matrix<A::Value> {
+    let size = matrix.len();
+    let mut result = (0..size)
+        .map(|row| {
+            (0..size)
+                .map(|col| {
+                    if row == col {
+                        arithmetic.one()
+                    } else {
+                        arithmetic.zero()
+                    }
+                })
+                .collect()
+        })
+        .collect::<Matrix<_>>();
+
+    let mut base = matrix.clone();
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = matrix_multiply(arithmetic, &result, &base);
+        }
+
+        base = matrix_multiply(arithmetic, &base, &base);
+        exponent >>= 1;
+    }
+
+    result
+}
+
+/// Build the matrix that moves the timer counts forward by one day, such that the counts on the next day are the
+/// product of this matrix and the counts on the current day
+fn build_transition_matrix<A: Arithmetic>(
+    arithmetic: &A,
+    life_cycle: &LifeCycle,
+) -> Matrix<A::Value> {
+    let num_timers = life_cycle.num_timers();
+    let mut matrix = vec![vec![arithmetic.zero(); num_timers]; num_timers];
+    // Every timer counts down by one...
+    for i in 1..num_timers {
+        matrix[i - 1][i] = arithmetic.one();
     }
 
-    day_map.iter().sum()
+    // ...except for the fish at zero, which reset and spawn a new fish. If these happen to be the same timer value,
+    // there are two fish for every one that was at zero.
+    matrix[life_cycle.reset_timer][0] = arithmetic.one();
+    matrix[life_cycle.spawn_timer()][0] =
+        arithmetic.add(&matrix[life_cycle.spawn_timer()][0], &arithmetic.one());
+
+    matrix
+}
+
+/// Compute the population after the given number of days in O(log n) time, by raising the daily transition matrix
+/// to the power of the number of days
+fn simulate_with_matrix<A: Arithmetic>(
+    arithmetic: &A,
+    input: &[u8],
+    life_cycle: &LifeCycle,
+    num_days: u64,
+) -> Result<A::Value, Error> {
+    let transition = matrix_power(
+        arithmetic,
+        &build_transition_matrix(arithmetic, life_cycle),
+        num_days,
+    );
+    let initial_counts = count_timers(input, life_cycle)?
+        .into_iter()
+        .map(|count| arithmetic.value_of(count))
+        .collect::<Vec<_>>();
+
+    let total = transition
+        .iter()
+        .flat_map(|row| row.iter().zip(&initial_counts))
+        .fold(arithmetic.zero(), |total, (factor, count)| {
+            arithmetic.add(&total, &arithmetic.mul(factor, count))
+        });
+
+    Ok(total)
 }
 
 fn main() {
@@ -43,6 +306,115 @@ fn main() {
         .collect::<Result<Vec<_>, _>>()
         .expect("Invalid number in input");
 
-    println!("Part 1: {}", simulate(&input, 80));
-    println!("Part 2: {}", simulate(&input, 256));
+    let mut life_cycle = LifeCycle::default();
+    let mut part1_days = 80;
+    let mut part2_days = 256;
+    let mut num_days = None;
+    let mut modulus = None;
+    let mut time_series = None;
+    let mut args = env::args().skip(2);
+    while let Some(arg) = args.next() {
+        let mut next_number = |name: &str| -> usize {
+            args.next()
+                .and_then(|n| n.parse().ok())
+                .unwrap_or_else(|| panic!("{name} must be a non-negative integer"))
+        };
+
+        match arg.as_str() {
+            "--reset-timer" => life_cycle.reset_timer = next_number("Reset timer"),
+            "--newborn-timer" => life_cycle.newborn_timer = next_number("Newborn timer"),
+            "--maturation-delay" => life_cycle.maturation_delay = next_number("Maturation delay"),
+            "--part1-days" => {
+                part1_days = u32::try_from(next_number("Number of days"))
+                    .expect("Number of days is too large");
+            }
+            "--part2-days" => {
+                part2_days = u32::try_from(next_number("Number of days"))
+                    .expect("Number of days is too large");
+            }
+            "--days" => {
+                num_days = Some(
+                    args.next()
+                        .and_then(|n| n.parse::<u64>().ok())
+                        .expect("Number of days must be a non-negative integer"),
+                );
+            }
+            "--modulus" => {
+                modulus = Some(
+                    args.next()
+                        .and_then(|n| n.parse::<u64>().ok())
+                        .filter(|&n| n > 0)
+                        .expect("Modulus must be a positive integer"),
+                );
+            }
+            "--time-series" => {
+                let series_days = u32::try_from(next_number("Number of days"))
+                    .expect("Number of days is too large");
+                let path = args
+                    .next()
+                    .expect("No time series output filename specified");
+                time_series = Some((series_days, path));
+            }
+            _ => panic!("Unknown argument '{arg}'"),
+        }
+    }
+
+    // The life cycle can make the population grow far faster than a lanternfish's, so the counts must be exact
+    for (name, part_days) in [("Part 1", part1_days), ("Part 2", part2_days)] {
+        let population =
+            simulate_with_matrix(&ExactArithmetic, &input, &life_cycle, u64::from(part_days))
+                .unwrap_or_else(|err| panic!("Invalid input: {err}"));
+        println!("{name}: {population}");
+    }
+
+    if let Some(num_days) = num_days {
+        match modulus {
+            Some(modulus) => println!(
+                "After {num_days} days (mod {modulus}): {}",
+                simulate_with_matrix(&ModularArithmetic(modulus), &input, &life_cycle, num_days)
+                    .unwrap_or_else(|err| panic!("Invalid input: {err}"))
+            ),
+            None => println!(
+                "After {num_days} days: {}",
+                simulate_with_matrix(&ExactArithmetic, &input, &life_cycle, num_days)
+                    .unwrap_or_else(|err| panic!("Invalid input: {err}"))
+            ),
+        }
+    }
+
+    if let Some((series_days, path)) = time_series {
+        let output_file = File::create(path).expect("Could not create time series file");
+        write_time_series(output_file, &input, &life_cycle, series_days)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_series_is_exact_past_u64_overflow() {
+        let input = [3, 4, 3, 1, 2];
+        let life_cycle = LifeCycle::default();
+        let mut csv = Vec::new();
+        write_time_series(&mut csv, &input, &life_cycle, 600).unwrap();
+
+        let csv = String::from_utf8(csv).unwrap();
+        let records = csv
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 601);
+
+        for (day, record) in records.iter().enumerate() {
+            let expected =
+                simulate_with_matrix(&ExactArithmetic, &input, &life_cycle, day as u64).unwrap();
+            assert_eq!(record[0], day.to_string());
+            assert_eq!(record[1], expected.to_string(), "day {day}");
+        }
+
+        assert!(records[600][2].starts_with("1.0"));
+    }
 }
@@ -1,13 +1,325 @@
 #![warn(clippy::all, clippy::pedantic)]
+use nom::{
+    character::complete::{digit1, line_ending, multispace0, space1},
+    combinator::{eof, map_res},
+    multi::separated_list1,
+    sequence::{separated_pair, terminated, tuple},
+    IResult,
+};
 use std::env;
-use std::fs;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io;
+use std::ops::RangeInclusive;
+use thiserror::Error;
 
-enum Part {
-    Part1,
-    Part2,
+#[derive(Debug, Error)]
+enum Error {
+    #[error("Unknown cost function '{0}'")]
+    UnknownCostFunction(String),
+    #[error("The first segment of a piecewise cost table must start at step 1, not step {0}")]
+    PiecewiseStartsAfterOne(u64),
+    #[error(
+        "Segments of a piecewise cost table must start at increasing steps, but {1} came after {0}"
+    )]
+    PiecewiseNotIncreasing(u64, u64),
 }
 
-fn run(input: &[i32], part: &Part) -> i32 {
+/// A segment of a piecewise cost table: every step from `start_step` onwards (until the next segment) costs
+/// `cost_per_step`
+#[derive(Debug, Clone, Copy)]
+struct PiecewiseSegment {
+    start_step: u64,
+    cost_per_step: u64,
+}
+
+/// The amount of fuel a crab spends to move a given number of steps
+#[derive(Debug, Clone)]
+enum CostFunction {
+    /// Each step costs one fuel
+    Linear,
+    /// Each step costs one more fuel than the last
+    Triangular,
+    /// Moving n steps costs n^2 fuel
+    Quadratic,
+    /// The given cost function, but never costing more than the cap
+    Capped(Box<CostFunction>, u64),
+    /// A user-defined table of the cost of each step, in segments ordered by their starting step
+    Piecewise(Vec<PiecewiseSegment>),
+}
+
+impl TryFrom<&str> for CostFunction {
+    type Error = Error;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        match name {
+            "linear" => Ok(Self::Linear),
+            "triangular" => Ok(Self::Triangular),
+            "quadratic" => Ok(Self::Quadratic),
+            _ => Err(Error::UnknownCostFunction(name.to_string())),
+        }
+    }
+}
+
+impl Display for CostFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Linear => write!(f, "linear"),
+            Self::Triangular => write!(f, "triangular"),
+            Self::Quadratic => write!(f, "quadratic"),
+            Self::Capped(cost_function, cap) => write!(f, "{cost_function} capped at {cap}"),
+            Self::Piecewise(segments) => write!(f, "piecewise ({} segments)", segments.len()),
+        }
+    }
+}
+
+impl CostFunction {
+    /// Build a piecewise cost function, ensuring that the segments cover every step in order
+    fn piecewise(segments: Vec<PiecewiseSegment>) -> Result<Self, Error> {
+        match segments.first() {
+            Some(first) if first.start_step != 1 => {
+                return Err(Error::PiecewiseStartsAfterOne(first.start_step))
+            }
+            _ => (),
+        }
+
+        if let Some(window) = segments
+            .windows(2)
+            .find(|window| window[0].start_step >= window[1].start_step)
+        {
+            return Err(Error::PiecewiseNotIncreasing(
+                window[0].start_step,
+                window[1].start_step,
+            ));
+        }
+
+        Ok(Self::Piecewise(segments))
+    }
+
+    /// Get the fuel needed to move the given number of steps
+    fn cost(&self, num_steps: u64) -> i128 {
+        let steps = i128::from(num_steps);
+        match self {
+            Self::Linear => steps,
+            // 1 + 2 + 3 + ... + n => n(n+1)/2
+            Self::Triangular => steps * (steps + 1) / 2,
+            Self::Quadratic => steps * steps,
+            Self::Capped(cost_function, cap) => cost_function.cost(num_steps).min(i128::from(*cap)),
+            Self::Piecewise(segments) => segments
+                .iter()
+                .zip(
+                    segments
+                        .iter()
+                        .skip(1)
+                        .map(|next| i128::from(next.start_step)),
+                )
+                .chain(segments.last().map(|last| (last, i128::MAX)))
+                .map(|(segment, end_step)| {
+                    // The number of steps that fall in this segment, start_step..end_step
+                    let steps_in_segment =
+                        (steps.min(end_step - 1) - i128::from(segment.start_step) + 1).max(0);
+
+                    steps_in_segment * i128::from(segment.cost_per_step)
+                })
+                .sum(),
+        }
+    }
+
+    /// Check whether the total cost of moving every crab to a destination is convex in the destination. This holds
+    /// exactly when the cost of each successive step never decreases.
+    fn is_convex(&self) -> bool {
+        match self {
+            Self::Linear | Self::Triangular | Self::Quadratic => true,
+            Self::Capped(..) => false,
+            Self::Piecewise(segments) => segments
+                .windows(2)
+                .all(|window| window[0].cost_per_step <= window[1].cost_per_step),
+        }
+    }
+}
+
+/// The method used to find the optimal destination
+#[derive(Debug, Clone, Copy)]
+enum SearchStrategy {
+    /// The median, which is optimal for linear costs
+    Median,
+    /// A check of the positions around the mean, which is optimal for triangular and quadratic costs
+    Mean,
+    /// A search for where a convex cost stops decreasing
+    TernarySearch,
+    /// An evaluation of every position, which is needed if the cost is not convex
+    ExhaustiveScan,
+}
+
+impl Display for SearchStrategy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Median => "median",
+            Self::Mean => "mean",
+            Self::TernarySearch => "ternary search",
+            Self::ExhaustiveScan => "exhaustive scan",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// The result of optimising the crabs' alignment
+struct Alignment {
+    /// Every destination with the smallest total cost, as ranges in ascending order
+    optimal_destinations: Vec<RangeInclusive<i64>>,
+    total_cost: i128,
+    strategy: SearchStrategy,
+}
+
+impl Alignment {
+    /// Get the leftmost optimal destination
+    fn destination(&self) -> i64 {
+        *self.optimal_destinations[0].start()
+    }
+}
+
+/// Calculate the total fuel needed for every crab to move to the given destination
+fn total_cost(input: &[i64], destination: i64, cost_function: &CostFunction) -> i128 {
+    input
+        .iter()
+        .map(|&crab_location| cost_function.cost(crab_location.abs_diff(destination)))
+        .sum()
+}
+
+/// Find the median of the crab positions. The sum of absolute distances is minimized at any median, so this is the
+/// optimal destination for linear costs.
+fn median(input: &[i64]) -> i64 {
+    let mut sorted = input.to_vec();
+    sorted.sort_unstable();
+
+    sorted[sorted.len() / 2]
+}
+
+/// Find the floor of the mean of the crab positions
+fn floor_mean(input: &[i64]) -> i64 {
+    let sum = input.iter().map(|&n| i128::from(n)).sum::<i128>();
+    let len = i128::try_from(input.len()).expect("Input is too long");
+
+    i64::try_from(sum.div_euclid(len)).expect("Mean must be within the range of the input")
+}
+
+/// Find the destination with the smallest total cost by searching for where the (convex) cost stops decreasing.
+/// This only evaluates the cost `O(log(high - low))` times.
+fn ternary_search(input: &[i64], cost_function: &CostFunction, mut low: i64, mut high: i64) -> i64 {
+    // Invariant: the optimal destination lies within low..=high
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if total_cost(input, mid, cost_function) <= total_cost(input, mid + 1, cost_function) {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+
+    low
+}
+
+/// Find the optimal destination for a triangular or quadratic cost. For a triangular cost, the derivative of the
+/// total at `x` is `sum(x - c) + sum(sign(x - c)) / 2`, the second term of which is at most `n / 2` in magnitude,
+/// so the real-valued minimum is within half a position of the mean (and a quadratic cost is minimized exactly at
+/// the mean). As the cost is convex, the optimal integer destination must then be within one position of the floor
+/// of the mean.
+///
+/// Returns `None` if the candidate could not be proven optimal.
+fn mean_destination(
+    input: &[i64],
+    cost_function: &CostFunction,
+    smallest: i64,
+    largest: i64,
+) -> Option<i64> {
+    let mean = floor_mean(input);
+    let candidate = (mean - 1..=mean + 1)
+        .map(|destination| destination.clamp(smallest, largest))
+        .min_by_key(|&destination| total_cost(input, destination, cost_function))
+        // We already know there will be at least one element
+        .unwrap();
+
+    // A local minimum of a convex function is a global minimum, so checking the neighbours proves the candidate is
+    // optimal.
+    let cost = total_cost(input, candidate, cost_function);
+    [candidate - 1, candidate + 1]
+        .iter()
+        .all(|&neighbour| total_cost(input, neighbour, cost_function) >= cost)
+        .then_some(candidate)
+}
+
+/// Find the full range of destinations that share the optimal cost of the given destination. As the cost is convex,
+/// it never increases to the left of the optimum, and never decreases to the right of it, so the edges of the range
+/// can be found with a binary search.
+fn optimal_range_around(
+    input: &[i64],
+    cost_function: &CostFunction,
+    destination: i64,
+    smallest: i64,
+    largest: i64,
+) -> RangeInclusive<i64> {
+    let cost = total_cost(input, destination, cost_function);
+
+    // Find the leftmost destination in smallest..=destination with the optimal cost
+    let (mut low, mut high) = (smallest, destination);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if total_cost(input, mid, cost_function) == cost {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    let start = low;
+
+    // Find the rightmost destination in destination..=largest with the optimal cost
+    let (mut low, mut high) = (destination, largest);
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        if total_cost(input, mid, cost_function) == cost {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    start..=low
+}
+
+/// Find every destination with the optimal cost by evaluating every position, which works for any cost function
+fn exhaustive_optimal_ranges(
+    input: &[i64],
+    cost_function: &CostFunction,
+    smallest: i64,
+    largest: i64,
+) -> Vec<RangeInclusive<i64>> {
+    let mut best_cost = None;
+    let mut ranges: Vec<RangeInclusive<i64>> = Vec::new();
+    for destination in smallest..=largest {
+        let cost = total_cost(input, destination, cost_function);
+        match best_cost {
+            Some(best) if cost > best => (),
+            Some(best) if cost == best => match ranges.last_mut() {
+                // Extend the current run of optimal destinations if this one is adjacent to it
+                Some(range) if *range.end() == destination - 1 => {
+                    *range = *range.start()..=destination;
+                }
+                _ => ranges.push(destination..=destination),
+            },
+            _ => {
+                best_cost = Some(cost);
+                ranges = vec![destination..=destination];
+            }
+        }
+    }
+
+    ranges
+}
+
+/// Find the destinations that require the least total fuel, using the fastest search strategy the cost function
+/// allows
+fn optimise(input: &[i64], cost_function: &CostFunction) -> Alignment {
     let smallest = *input
         .iter()
         .min()
@@ -17,28 +329,144 @@ fn run(input: &[i32], part: &Part) -> i32 {
         .max()
         .expect("Input should have more than zero elements");
 
-    // This assumes that the answer is in the range of the min/max of the crab positions,
-    // but that seems exceedingly likely. If this puzzle were more complex I might add/sub
-    // "largest" on each of the bounds
-    //
-    // Honestly I didn't expect this to work on both parts
-    (smallest..largest)
-        .map(|possible_destination| {
-            input
-                .iter()
-                .map(|crab_location| {
-                    let steps = (crab_location - possible_destination).abs();
-                    match part {
-                        Part::Part1 => steps,
-                        // 1 + 2 + 3 + ... + n => n(n+1)/2
-                        Part::Part2 => steps * (steps + 1) / 2,
-                    }
-                })
-                .sum::<i32>()
+    // None of the cost functions decrease with more steps, and moving outside of smallest..=largest only moves every
+    // crab further, so the optimum is always within the range of the crab positions.
+    let convex_destination = match cost_function {
+        CostFunction::Linear => Some((median(input), SearchStrategy::Median)),
+        CostFunction::Triangular | CostFunction::Quadratic => Some(
+            mean_destination(input, cost_function, smallest, largest).map_or_else(
+                || {
+                    (
+                        ternary_search(input, cost_function, smallest, largest),
+                        SearchStrategy::TernarySearch,
+                    )
+                },
+                |destination| (destination, SearchStrategy::Mean),
+            ),
+        ),
+        _ if cost_function.is_convex() => Some((
+            ternary_search(input, cost_function, smallest, largest),
+            SearchStrategy::TernarySearch,
+        )),
+        _ => None,
+    };
+
+    let (optimal_destinations, strategy) = match convex_destination {
+        Some((destination, strategy)) => (
+            vec![optimal_range_around(
+                input,
+                cost_function,
+                destination,
+                smallest,
+                largest,
+            )],
+            strategy,
+        ),
+        None => (
+            exhaustive_optimal_ranges(input, cost_function, smallest, largest),
+            SearchStrategy::ExhaustiveScan,
+        ),
+    };
+
+    let total_cost = total_cost(input, *optimal_destinations[0].start(), cost_function);
+    Alignment {
+        optimal_destinations,
+        total_cost,
+        strategy,
+    }
+}
+
+/// Format the optimal destinations as a comma-separated list, collapsing runs into ranges
+fn format_destinations(destinations: &[RangeInclusive<i64>]) -> String {
+    destinations
+        .iter()
+        .map(|range| {
+            if range.start() == range.end() {
+                range.start().to_string()
+            } else {
+                format!("{}..={}", range.start(), range.end())
+            }
         })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Print the fuel each crab spends moving to the given destination
+fn print_fuel_breakdown(input: &[i64], cost_function: &CostFunction, destination: i64) {
+    println!("Fuel spent by each crab moving to {destination}:");
+    println!("  crab  position  steps  fuel");
+    for (crab, &crab_location) in input.iter().enumerate() {
+        let steps = crab_location.abs_diff(destination);
+        println!(
+            "  {:>4}  {:>8}  {:>5}  {}",
+            crab,
+            crab_location,
+            steps,
+            cost_function.cost(steps)
+        );
+    }
+}
+
+/// Write the total cost of every candidate destination (the range of the crab positions) as CSV
+fn write_cost_curve<W: io::Write>(
+    writer: W,
+    input: &[i64],
+    cost_function: &CostFunction,
+    alignment: &Alignment,
+) -> Result<(), csv::Error> {
+    let smallest = *input
+        .iter()
         .min()
-        // We already know there will be at least one element
-        .unwrap()
+        .expect("Input should have more than zero elements");
+    let largest = *input
+        .iter()
+        .max()
+        .expect("Input should have more than zero elements");
+
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(["position", "total_cost", "optimal"])?;
+    for destination in smallest..=largest {
+        let is_optimal = alignment
+            .optimal_destinations
+            .iter()
+            .any(|range| range.contains(&destination));
+
+        writer.write_record([
+            destination.to_string(),
+            total_cost(input, destination, cost_function).to_string(),
+            is_optimal.to_string(),
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn parse_number(input: &str) -> IResult<&str, u64> {
+    map_res(digit1, str::parse)(input)
+}
+
+/// Parse a piecewise cost table, where each line holds the step a segment starts at and the fuel each step in that
+/// segment costs
+fn parse_piecewise_table(input: &str) -> IResult<&str, Vec<PiecewiseSegment>> {
+    terminated(
+        separated_list1(
+            line_ending,
+            separated_pair(parse_number, space1, parse_number),
+        ),
+        tuple((multispace0, eof)),
+    )(input)
+    .map(|(remaining, pairs)| {
+        let segments = pairs
+            .into_iter()
+            .map(|(start_step, cost_per_step)| PiecewiseSegment {
+                start_step,
+                cost_per_step,
+            })
+            .collect();
+
+        (remaining, segments)
+    })
 }
 
 fn main() {
@@ -48,10 +476,83 @@ fn main() {
     let input = raw_input
         .trim_end()
         .split(',')
-        .map(str::parse::<i32>)
+        .map(str::parse::<i64>)
         .collect::<Result<Vec<_>, _>>()
         .expect("Invalid number in input");
 
-    println!("Part 1: {}", run(&input, &Part::Part1));
-    println!("Part 2: {}", run(&input, &Part::Part2));
+    let mut cost_function = None;
+    let mut cap = None;
+    let mut show_breakdown = false;
+    let mut curve_file_name = None;
+    let mut args = env::args().skip(2);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cost" => {
+                let name = args.next().expect("No cost function specified");
+                cost_function = Some(if name == "piecewise" {
+                    let table_file_name = args.next().expect("No piecewise table file specified");
+                    let raw_table = fs::read_to_string(table_file_name)
+                        .expect("Failed to read piecewise table file");
+                    let (_, segments) =
+                        parse_piecewise_table(&raw_table).expect("Failed to parse piecewise table");
+
+                    CostFunction::piecewise(segments).expect("Invalid piecewise table")
+                } else {
+                    CostFunction::try_from(name.as_str()).expect("Invalid cost function")
+                });
+            }
+            "--cap" => {
+                cap = Some(
+                    args.next()
+                        .and_then(|n| n.parse::<u64>().ok())
+                        .expect("Cap must be a non-negative integer"),
+                );
+            }
+            "--breakdown" => show_breakdown = true,
+            "--curve" => {
+                curve_file_name = Some(
+                    args.next()
+                        .expect("No cost curve output filename specified"),
+                );
+            }
+            _ => panic!("Unknown argument '{arg}'"),
+        }
+    }
+
+    println!(
+        "Part 1: {}",
+        optimise(&input, &CostFunction::Linear).total_cost
+    );
+    println!(
+        "Part 2: {}",
+        optimise(&input, &CostFunction::Triangular).total_cost
+    );
+
+    let is_custom_cost = cost_function.is_some() || cap.is_some();
+    if is_custom_cost || show_breakdown || curve_file_name.is_some() {
+        // Without a custom cost function, give the details for part 2 (capped, if a cap was given)
+        let mut cost_function = cost_function.unwrap_or(CostFunction::Triangular);
+        if let Some(cap) = cap {
+            cost_function = CostFunction::Capped(Box::new(cost_function), cap);
+        }
+
+        let alignment = optimise(&input, &cost_function);
+        println!(
+            "Cost ({cost_function}): {} at position(s) {} (found by {})",
+            alignment.total_cost,
+            format_destinations(&alignment.optimal_destinations),
+            alignment.strategy
+        );
+
+        if show_breakdown {
+            print_fuel_breakdown(&input, &cost_function, alignment.destination());
+        }
+
+        if let Some(curve_file_name) = curve_file_name {
+            let output_file =
+                File::create(curve_file_name).expect("Could not create cost curve file");
+            write_cost_curve(output_file, &input, &cost_function, &alignment)
+                .expect("Failed to write cost curve");
+        }
+    }
 }
@@ -1,15 +1,16 @@
 #![warn(clippy::all, clippy::pedantic)]
 use std::collections::{HashMap, HashSet};
 use std::env;
-use std::fs::File;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
 use std::io::{BufRead, BufReader};
 
 use nom::{
     bytes::complete::{tag, take_while1},
-    character::complete::char,
+    character::complete::{char, satisfy, space1},
     combinator::eof,
     multi::separated_list1,
-    sequence::{separated_pair, terminated},
+    sequence::{pair, preceded, separated_pair, terminated},
     IResult,
 };
 
@@ -17,6 +18,8 @@ use itertools::Itertools;
 use thiserror::Error;
 
 const SEGMENT_CHARS: &[char] = &['a', 'b', 'c', 'd', 'e', 'f', 'g'];
+/// The most segments a display can have, as each wire is named by a letter
+const MAX_SEGMENTS: usize = 26;
 
 // A wrapper for println that only prints in debug mode
 macro_rules! dprintln {
@@ -46,123 +49,394 @@ struct SevenSegmentSignals {
 
 #[derive(Error, Debug)]
 enum DecodeError {
-    #[error("Invalid configuraton string given: {0}")]
-    InavlidConfiguration(String),
+    #[error("Pattern '{0}' uses a wire that is not part of the display")]
+    UnknownWire(String),
+    #[error("Pattern '{pattern}' lights {size} segments, but no symbol does")]
+    NoGlyphOfSize { pattern: String, size: u32 },
+    #[error("No wiring is consistent with every signal pattern")]
+    Unsolvable,
+    #[error("More than one wiring is consistent with the signal patterns: {}", .0.iter().join(", "))]
+    Ambiguous(Vec<Conflict>),
+    #[error("Pattern '{0}' does not match any symbol")]
+    UnknownPattern(String),
 }
 
-impl SevenSegmentSignals {
-    fn decode_str(&self, s: &str) -> Result<u8, DecodeError> {
-        // Could these be members? yes. Are they? no.
-        let zero = Self::make_segment_str(&[
-            self.top,
-            self.top_left,
-            self.bottom_left,
-            self.bottom,
-            self.bottom_right,
-            self.top_right,
-        ]);
+/// A symbol that would be shown as something else by another wiring consistent with the same signal patterns
+#[derive(Debug)]
+struct Conflict {
+    symbol: char,
+    /// The symbol shown by the other wiring, or None if it would not show a valid symbol
+    alternative: Option<char>,
+}
 
-        let one = Self::make_segment_str(&[self.top_right, self.bottom_right]);
+impl Display for Conflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.alternative {
+            Some(alternative) => write!(f, "'{}' could also be '{alternative}'", self.symbol),
+            None => write!(f, "'{}' could also be an invalid pattern", self.symbol),
+        }
+    }
+}
 
-        let two = Self::make_segment_str(&[
-            self.top,
-            self.top_right,
-            self.middle,
-            self.bottom_left,
-            self.bottom,
-        ]);
+#[derive(Error, Debug)]
+enum DefinitionError {
+    #[error("The display definition does not list its segments")]
+    MissingSegments,
+    #[error("Could not parse line {0} of the display definition")]
+    InvalidLine(usize),
+    #[error("The display has {0} segments, but at most {MAX_SEGMENTS} are supported")]
+    TooManySegments(usize),
+    #[error("Symbol '{0}' lights unknown segment '{1}'")]
+    UnknownSegment(char, String),
+    #[error("Symbol '{0}' is defined more than once")]
+    DuplicateSymbol(char),
+    #[error("Symbols '{0}' and '{1}' light the same segments")]
+    DuplicateGlyph(char, char),
+}
 
-        let three = Self::make_segment_str(&[
+impl SevenSegmentSignals {
+    /// Convert the deduced segments to a wiring for the standard seven segment display
+    fn to_wiring(&self) -> Wiring {
+        // This must match the order of the segments in `DisplayDefinition::seven_segment`
+        let wires = [
             self.top,
             self.top_right,
-            self.middle,
             self.bottom_right,
             self.bottom,
-        ]);
-
-        let four = Self::make_segment_str(&[
+            self.bottom_left,
             self.top_left,
             self.middle,
-            self.top_right,
-            self.bottom_right,
-        ]);
+        ];
 
-        let five = Self::make_segment_str(&[
-            self.top,
-            self.top_left,
-            self.middle,
-            self.bottom_right,
-            self.bottom,
-        ]);
+        let mut segment_for_wire = vec![0; wires.len()];
+        for (segment, &wire) in wires.iter().enumerate() {
+            segment_for_wire[wire_index(wire)] = segment;
+        }
 
-        let six = Self::make_segment_str(&[
-            self.top,
-            self.top_left,
-            self.middle,
-            self.bottom_left,
-            self.bottom,
-            self.bottom_right,
-        ]);
+        Wiring { segment_for_wire }
+    }
+}
 
-        let seven = Self::make_segment_str(&[self.top, self.top_right, self.bottom_right]);
+/// How the wiring of each display is determined
+#[derive(Debug, Clone, Copy)]
+enum SolvingMethod {
+    /// Deduce the wiring by hand, which only works for the seven segment display
+    Deduction,
+    /// Search for the wiring with the general constraint solver
+    ConstraintSolver,
+    /// Use both methods, and ensure they agree
+    CrossCheck,
+}
 
-        let eight = Self::make_segment_str(&[
-            self.top,
-            self.top_left,
-            self.bottom_left,
-            self.middle,
-            self.bottom,
-            self.bottom_right,
-            self.top_right,
-        ]);
+/// A symbol that a display can show
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Glyph {
+    symbol: char,
+    /// A bitmask of the segments this symbol lights, indexed by the segments of the display
+    segments: u32,
+}
 
-        let nine = Self::make_segment_str(&[
-            self.top,
-            self.top_left,
-            self.middle,
-            self.bottom,
-            self.bottom_right,
-            self.top_right,
-        ]);
-
-        let sorted_str = s.chars().sorted().collect::<String>();
-        // Could this be a map? Yes. Is it? No.
-        if sorted_str == one {
-            Ok(1)
-        } else if sorted_str == two {
-            Ok(2)
-        } else if sorted_str == three {
-            Ok(3)
-        } else if sorted_str == four {
-            Ok(4)
-        } else if sorted_str == five {
-            Ok(5)
-        } else if sorted_str == six {
-            Ok(6)
-        } else if sorted_str == seven {
-            Ok(7)
-        } else if sorted_str == eight {
-            Ok(8)
-        } else if sorted_str == nine {
-            Ok(9)
-        } else if sorted_str == zero {
-            Ok(0)
-        } else {
-            Err(DecodeError::InavlidConfiguration(s.to_string()))
+/// A display, made up of segments, along with the segments each of its symbols lights
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DisplayDefinition {
+    segment_names: Vec<String>,
+    glyphs: Vec<Glyph>,
+}
+
+impl DisplayDefinition {
+    /// The standard seven segment display, which shows the digits 0-9
+    fn seven_segment() -> Self {
+        Self::parse(include_str!("../displays/seven_segment.txt"))
+            .expect("built-in seven segment definition is valid")
+    }
+
+    /// Parse a display definition. The first line lists every segment of the display, and each line after it gives a
+    /// symbol, followed by the segments that symbol lights. Blank lines and lines starting with '#' are ignored.
+    fn parse(input: &str) -> Result<Self, DefinitionError> {
+        let mut lines = input
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        let (line_number, header) = lines.next().ok_or(DefinitionError::MissingSegments)?;
+        let (_, segment_names) =
+            parse_segments_line(header).map_err(|_| DefinitionError::InvalidLine(line_number))?;
+
+        // Each wire is a letter, and each glyph is stored as a bitmask
+        if segment_names.len() > MAX_SEGMENTS {
+            return Err(DefinitionError::TooManySegments(segment_names.len()));
+        }
+
+        let mut glyphs: Vec<Glyph> = Vec::new();
+        for (line_number, line) in lines {
+            let (_, (symbol, lit_segments)) =
+                parse_glyph_line(line).map_err(|_| DefinitionError::InvalidLine(line_number))?;
+
+            let mut segments = 0;
+            for name in lit_segments {
+                let segment = segment_names
+                    .iter()
+                    .position(|&segment_name| segment_name == name)
+                    .ok_or_else(|| DefinitionError::UnknownSegment(symbol, name.to_string()))?;
+
+                segments |= 1 << segment;
+            }
+
+            if let Some(existing) = glyphs.iter().find(|glyph| glyph.symbol == symbol) {
+                return Err(DefinitionError::DuplicateSymbol(existing.symbol));
+            } else if let Some(existing) = glyphs.iter().find(|glyph| glyph.segments == segments) {
+                return Err(DefinitionError::DuplicateGlyph(existing.symbol, symbol));
+            }
+
+            glyphs.push(Glyph { symbol, segments });
+        }
+
+        Ok(Self {
+            segment_names: segment_names.into_iter().map(str::to_owned).collect(),
+            glyphs,
+        })
+    }
+
+    fn num_segments(&self) -> usize {
+        self.segment_names.len()
+    }
+
+    /// Get the numbers of segments that are lit by exactly one glyph. A pattern of one of these sizes can be decoded
+    /// without knowing the wiring.
+    fn unique_glyph_sizes(&self) -> HashSet<u32> {
+        self.glyphs
+            .iter()
+            .map(|glyph| glyph.segments.count_ones())
+            .counts()
+            .into_iter()
+            .filter(|&(_, count)| count == 1)
+            .map(|(size, _)| size)
+            .collect()
+    }
+
+    /// Get all of the glyphs that light the given number of segments
+    fn glyphs_of_size(&self, size: u32) -> impl Iterator<Item = &Glyph> {
+        self.glyphs
+            .iter()
+            .filter(move |glyph| glyph.segments.count_ones() == size)
+    }
+}
+
+/// A mapping from each (scrambled) wire to the segment of the display it actually drives
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Wiring {
+    segment_for_wire: Vec<usize>,
+}
+
+impl Wiring {
+    /// Get the wires that light the given segments
+    fn wires_for(&self, segments: u32) -> u32 {
+        self.segment_for_wire
+            .iter()
+            .enumerate()
+            .filter(|&(_, &segment)| segments & 1 << segment != 0)
+            .fold(0, |mask, (wire, _)| mask | 1 << wire)
+    }
+
+    /// Build the table for decoding signals with this wiring
+    fn decoding_table(&self, definition: &DisplayDefinition) -> DecodingTable {
+        let symbol_for_wires = definition
+            .glyphs
+            .iter()
+            .map(|glyph| (self.wires_for(glyph.segments), glyph.symbol))
+            .collect();
+
+        DecodingTable { symbol_for_wires }
+    }
+
+    /// Find the symbols that this wiring and another would show differently for the same signal
+    fn conflicts_with(&self, other: &Wiring, definition: &DisplayDefinition) -> Vec<Conflict> {
+        let other_table = other.decoding_table(definition);
+        definition
+            .glyphs
+            .iter()
+            .map(|glyph| Conflict {
+                symbol: glyph.symbol,
+                alternative: other_table
+                    .symbol_for_wires
+                    .get(&self.wires_for(glyph.segments))
+                    .copied(),
+            })
+            .filter(|conflict| conflict.alternative != Some(conflict.symbol))
+            .collect()
+    }
+}
+
+/// A lookup table from the wires of a signal, as a bitmask, to the symbol they show
+struct DecodingTable {
+    symbol_for_wires: HashMap<u32, char>,
+}
+
+impl DecodingTable {
+    /// Decode the symbol shown by the given signal
+    fn decode_str(&self, s: &str) -> Result<char, DecodeError> {
+        self.symbol_for_wires
+            .get(&wire_mask(s))
+            .copied()
+            .ok_or_else(|| DecodeError::UnknownPattern(s.to_string()))
+    }
+}
+
+/// Searches for the wiring of a display that is consistent with a set of observed signal patterns. Each wire starts
+/// off able to drive any segment; the candidates are narrowed down by each pattern, and the rest of the search
+/// is done by backtracking.
+struct WiringSolver<'a> {
+    definition: &'a DisplayDefinition,
+    /// The observed patterns, as bitmasks of wires
+    patterns: Vec<u32>,
+    /// For each wire, a bitmask of the segments it could drive
+    candidates: Vec<u32>,
+}
+
+impl<'a> WiringSolver<'a> {
+    fn new(definition: &'a DisplayDefinition, signal_patterns: &[String]) -> Self {
+        let num_segments = definition.num_segments();
+        let all_segments = (1 << num_segments) - 1;
+        let patterns = signal_patterns
+            .iter()
+            .map(|pattern| wire_mask(pattern))
+            .collect::<Vec<_>>();
+
+        let mut candidates = vec![all_segments; num_segments];
+        for &pattern in &patterns {
+            let (union, intersection) = definition.glyphs_of_size(pattern.count_ones()).fold(
+                (0, all_segments),
+                |(union, intersection), glyph| {
+                    (union | glyph.segments, intersection & glyph.segments)
+                },
+            );
+
+            for (wire, wire_candidates) in candidates.iter_mut().enumerate() {
+                if pattern & 1 << wire == 0 {
+                    // A segment that every possible glyph lights must be driven by one of the pattern's wires
+                    *wire_candidates &= !intersection;
+                } else {
+                    // A wire in the pattern must drive a segment that at least one possible glyph lights
+                    *wire_candidates &= union;
+                }
+            }
+        }
+
+        Self {
+            definition,
+            patterns,
+            candidates,
         }
     }
 
-    fn make_segment_str(segments: &[char]) -> String {
-        segments.iter().sorted().collect::<String>()
+    /// Find the wirings consistent with every pattern, stopping once `limit` have been found
+    fn solve(&self, limit: usize) -> Vec<Wiring> {
+        let mut assignment = vec![None; self.definition.num_segments()];
+        let mut solutions = Vec::new();
+        self.search(&mut assignment, 0, &mut solutions, limit);
+
+        solutions
+    }
+
+    /// Find every completion of the given (partial) assignment of wires to segments, until `limit` solutions have been
+    /// found. `used` is the bitmask of segments already assigned to a wire. Returns whether the limit was reached.
+    fn search(
+        &self,
+        assignment: &mut [Option<usize>],
+        used: u32,
+        solutions: &mut Vec<Wiring>,
+        limit: usize,
+    ) -> bool {
+        // Assign the most constrained wire first, so dead ends are found as early as possible
+        let next_wire = assignment
+            .iter()
+            .enumerate()
+            .filter(|(_, segment)| segment.is_none())
+            .min_by_key(|&(wire, _)| (self.candidates[wire] & !used).count_ones())
+            .map(|(wire, _)| wire);
+
+        let Some(wire) = next_wire else {
+            // Every wire is assigned
+            let segment_for_wire = assignment.iter().map(|segment| segment.unwrap()).collect();
+            solutions.push(Wiring { segment_for_wire });
+            return solutions.len() >= limit;
+        };
+
+        let options = self.candidates[wire] & !used;
+        for segment in
+            (0..self.definition.num_segments()).filter(|&segment| options & 1 << segment != 0)
+        {
+            assignment[wire] = Some(segment);
+            if self.is_consistent(assignment)
+                && self.search(assignment, used | 1 << segment, solutions, limit)
+            {
+                assignment[wire] = None;
+                return true;
+            }
+        }
+
+        assignment[wire] = None;
+        false
+    }
+
+    /// Check that every pattern could still be a glyph under the given partial assignment
+    fn is_consistent(&self, assignment: &[Option<usize>]) -> bool {
+        self.patterns.iter().all(|&pattern| {
+            let (lit, unlit) = assignment.iter().enumerate().fold(
+                (0_u32, 0_u32),
+                |(lit, unlit), (wire, segment)| match segment {
+                    Some(segment) if pattern & 1 << wire != 0 => (lit | 1 << segment, unlit),
+                    Some(segment) => (lit, unlit | 1 << segment),
+                    None => (lit, unlit),
+                },
+            );
+
+            self.definition
+                .glyphs_of_size(pattern.count_ones())
+                .any(|glyph| glyph.segments & lit == lit && glyph.segments & unlit == 0)
+        })
     }
 }
 
-fn is_segment_char(c: char) -> bool {
-    SEGMENT_CHARS.contains(&c)
+/// Get the index of the given wire, where 'a' is the first
+fn wire_index(wire: char) -> usize {
+    (wire as usize) - ('a' as usize)
+}
+
+/// Convert a signal pattern to a bitmask of its wires
+fn wire_mask(pattern: &str) -> u32 {
+    pattern
+        .chars()
+        .fold(0, |mask, wire| mask | 1 << wire_index(wire))
 }
 
 fn parse_signal_block(chunk: &str) -> IResult<&str, &str> {
-    take_while1(is_segment_char)(chunk)
+    take_while1(|c: char| c.is_ascii_lowercase())(chunk)
+}
+
+fn is_segment_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn parse_segment_names(chunk: &str) -> IResult<&str, Vec<&str>> {
+    separated_list1(space1, take_while1(is_segment_name_char))(chunk)
+}
+
+/// Parse the line of a display definition listing all of its segments
+fn parse_segments_line(line: &str) -> IResult<&str, Vec<&str>> {
+    terminated(
+        preceded(pair(tag("segments"), space1), parse_segment_names),
+        eof,
+    )(line)
+}
+
+/// Parse a line of a display definition giving a symbol and the segments it lights
+fn parse_glyph_line(line: &str) -> IResult<&str, (char, Vec<&str>)> {
+    terminated(
+        separated_pair(satisfy(|c| !c.is_whitespace()), space1, parse_segment_names),
+        eof,
+    )(line)
 }
 
 fn parse_signal_list(chunk: &str) -> IResult<&str, Vec<&str>> {
@@ -207,21 +481,15 @@ fn transpose_signal_map(original: &HashMap<String, u8>) -> HashMap<u8, String> {
         .collect()
 }
 
-fn part1(signal_infos: &[SignalInfo]) -> usize {
+fn part1(signal_infos: &[SignalInfo], definition: &DisplayDefinition) -> usize {
+    let unique_sizes = definition.unique_glyph_sizes();
     signal_infos
         .iter()
-        .map(|signal_info| {
-            let signal_mapping = determine_simple_signal_mappings(&signal_info.signal_patterns);
-            signal_info
-                .output_values
-                .iter()
-                .filter(|output_value| {
-                    let sorted_output_value = output_value.chars().sorted().collect::<String>();
-                    signal_mapping.contains_key(&sorted_output_value)
-                })
-                .count()
+        .flat_map(|signal_info| &signal_info.output_values)
+        .filter(|output_value| {
+            u32::try_from(output_value.len()).is_ok_and(|size| unique_sizes.contains(&size))
         })
-        .sum()
+        .count()
 }
 
 fn make_char_set(s: &str) -> HashSet<char> {
@@ -229,46 +497,34 @@ fn make_char_set(s: &str) -> HashSet<char> {
 }
 
 // Infer all of the segements from a signal info
-// This is long and makes many assertions. I don't have the patience to clean it up at this moment.
-// If I was feeling less lazy, I'd turn these assertions into Results on the error type. Maybe later.
+// This is long and makes many checks along the way. If any of them fail, this gives up and returns None; the
+// constraint solver can then work out exactly what is wrong with the signal info.
 //
 // This is just advent of code after all :)
 #[allow(clippy::too_many_lines)]
-fn infer_segments(signal_info: &SignalInfo) -> SevenSegmentSignals {
+fn infer_segments(signal_info: &SignalInfo) -> Option<SevenSegmentSignals> {
     let signal_mapping = determine_simple_signal_mappings(&signal_info.signal_patterns);
     let num_to_signal_map = transpose_signal_map(&signal_mapping);
 
-    let one_signals = make_char_set(
-        num_to_signal_map
-            .get(&1)
-            .expect("no mapping for 1 was determined"),
-    );
-    let seven_signals = make_char_set(
-        num_to_signal_map
-            .get(&7)
-            .expect("no mapping for 7 was determined"),
-    );
+    let one_signals = make_char_set(num_to_signal_map.get(&1)?);
+    let seven_signals = make_char_set(num_to_signal_map.get(&7)?);
     let seven_one_difference = seven_signals
         .difference(&one_signals)
         .copied()
         .collect::<HashSet<_>>();
-    assert_eq!(seven_one_difference.len(), 1);
+    (seven_one_difference.len() == 1).then_some(())?;
 
     let top_segment = *seven_one_difference.iter().next().unwrap();
     dprintln!("top => {}", top_segment);
 
-    let four_signals = make_char_set(
-        num_to_signal_map
-            .get(&4)
-            .expect("no mapping for 4 was determined"),
-    );
+    let four_signals = make_char_set(num_to_signal_map.get(&4)?);
 
     // This will have the two segments that don't have the right "stick" of the four.
     let four_one_difference = four_signals
         .difference(&one_signals)
         .copied()
         .collect::<HashSet<_>>();
-    assert_eq!(four_one_difference.len(), 2);
+    (four_one_difference.len() == 2).then_some(())?;
 
     // There are three items that use six segments: 0 and 6, 9. Only zero matches only the lefthand "prong" of the four,
     // so the one with one intersection will disambiguate that one
@@ -278,7 +534,7 @@ fn infer_segments(signal_info: &SignalInfo) -> SevenSegmentSignals {
         .filter(|s| s.len() == 6)
         .map(|s| make_char_set(s))
         .collect::<Vec<_>>();
-    assert_eq!(six_element_char_sets.len(), 3);
+    (six_element_char_sets.len() == 3).then_some(())?;
 
     let one_element_left_from_six_set = six_element_char_sets
         .iter()
@@ -290,9 +546,9 @@ fn infer_segments(signal_info: &SignalInfo) -> SevenSegmentSignals {
         })
         .filter(|set| set.len() == 1)
         .collect::<Vec<_>>();
-    assert_eq!(one_element_left_from_six_set.len(), 1);
+    (one_element_left_from_six_set.len() == 1).then_some(())?;
     let middle_segment_set = &one_element_left_from_six_set[0];
-    assert_eq!(middle_segment_set.len(), 1);
+    (middle_segment_set.len() == 1).then_some(())?;
     let middle_segment = *middle_segment_set.iter().next().unwrap();
     dprintln!("middle => {}", middle_segment);
 
@@ -301,7 +557,7 @@ fn infer_segments(signal_info: &SignalInfo) -> SevenSegmentSignals {
         .iter()
         .filter(|&&c| c != middle_segment)
         .collect::<Vec<_>>();
-    assert_eq!(top_left_segment_set.len(), 1);
+    (top_left_segment_set.len() == 1).then_some(())?;
     let top_left_segment = *top_left_segment_set[0];
     dprintln!("top left => {}", top_left_segment);
 
@@ -311,13 +567,13 @@ fn infer_segments(signal_info: &SignalInfo) -> SevenSegmentSignals {
         .iter()
         .filter(|set| !set.is_superset(&one_signals))
         .collect::<Vec<_>>();
-    assert_eq!(five_signals_set.len(), 1);
+    (five_signals_set.len() == 1).then_some(())?;
     let five_signals = five_signals_set[0];
     let top_right_difference = one_signals
         .difference(five_signals)
         .copied()
         .collect::<HashSet<_>>();
-    assert_eq!(top_right_difference.len(), 1);
+    (top_right_difference.len() == 1).then_some(())?;
     let top_right_segment = *top_right_difference.iter().next().unwrap();
     dprintln!("top right => {}", top_right_segment);
 
@@ -336,7 +592,7 @@ fn infer_segments(signal_info: &SignalInfo) -> SevenSegmentSignals {
         .iter()
         .filter(|set| set.contains(&top_right_segment) && set.contains(&middle_segment))
         .collect::<Vec<_>>();
-    assert_eq!(nine_char_set_set.len(), 1);
+    (nine_char_set_set.len() == 1).then_some(())?;
 
     let nine_char_set = nine_char_set_set[0];
     let segment_chars_set = SEGMENT_CHARS.iter().copied().collect::<HashSet<_>>();
@@ -345,7 +601,7 @@ fn infer_segments(signal_info: &SignalInfo) -> SevenSegmentSignals {
         .difference(nine_char_set)
         .copied()
         .collect::<HashSet<_>>();
-    assert_eq!(bottom_left_set.len(), 1);
+    (bottom_left_set.len() == 1).then_some(())?;
     let bottom_left_segment = *bottom_left_set.iter().next().unwrap();
     dprintln!("bottom_left => {}", bottom_left_segment);
 
@@ -365,13 +621,13 @@ fn infer_segments(signal_info: &SignalInfo) -> SevenSegmentSignals {
         .difference(&all_but_bottom)
         .copied()
         .collect::<HashSet<_>>();
-    assert_eq!(bottom_set.len(), 1);
+    (bottom_set.len() == 1).then_some(())?;
 
     let bottom_segment = *bottom_set.iter().next().unwrap();
 
     dprintln!("bottom => {}", bottom_segment);
 
-    SevenSegmentSignals {
+    Some(SevenSegmentSignals {
         top: top_segment,
         top_right: top_right_segment,
         bottom_right: bottom_right_segment,
@@ -379,10 +635,93 @@ fn infer_segments(signal_info: &SignalInfo) -> SevenSegmentSignals {
         bottom_left: bottom_left_segment,
         top_left: top_left_segment,
         middle: middle_segment,
+    })
+}
+
+/// Find the only wiring consistent with the signal patterns using the constraint solver, or describe why there isn't
+/// one
+fn solve_unique_wiring(
+    definition: &DisplayDefinition,
+    signal_patterns: &[String],
+) -> Result<Wiring, DecodeError> {
+    for pattern in signal_patterns {
+        if pattern
+            .chars()
+            .any(|wire| wire_index(wire) >= definition.num_segments())
+        {
+            return Err(DecodeError::UnknownWire(pattern.clone()));
+        }
+
+        let size = u32::try_from(pattern.len()).unwrap_or(u32::MAX);
+        if definition.glyphs_of_size(size).next().is_none() {
+            return Err(DecodeError::NoGlyphOfSize {
+                pattern: pattern.clone(),
+                size,
+            });
+        }
+    }
+
+    // Finding a second wiring is enough to know the patterns are ambiguous
+    let mut solutions = WiringSolver::new(definition, signal_patterns)
+        .solve(2)
+        .into_iter();
+    match (solutions.next(), solutions.next()) {
+        (None, _) => Err(DecodeError::Unsolvable),
+        (Some(wiring), None) => Ok(wiring),
+        (Some(wiring), Some(other)) => Err(DecodeError::Ambiguous(
+            wiring.conflicts_with(&other, definition),
+        )),
+    }
+}
+
+/// Determine the wiring of a single display with the given method
+fn solve_wiring(
+    definition: &DisplayDefinition,
+    signal_info: &SignalInfo,
+    method: SolvingMethod,
+) -> Result<Wiring, DecodeError> {
+    // The deduction doesn't check every pattern along the way, so make sure its wiring decodes all of them
+    let deduce = || {
+        infer_segments(signal_info)
+            .map(|segments| segments.to_wiring())
+            .filter(|wiring| {
+                let table = wiring.decoding_table(definition);
+                signal_info
+                    .signal_patterns
+                    .iter()
+                    .all(|pattern| table.decode_str(pattern).is_ok())
+            })
+    };
+
+    match method {
+        // If the deduction fails, the constraint solver can tell us why
+        SolvingMethod::Deduction => deduce().map_or_else(
+            || solve_unique_wiring(definition, &signal_info.signal_patterns),
+            Ok,
+        ),
+        SolvingMethod::ConstraintSolver => {
+            solve_unique_wiring(definition, &signal_info.signal_patterns)
+        }
+        SolvingMethod::CrossCheck => {
+            let solved = solve_unique_wiring(definition, &signal_info.signal_patterns);
+            if let (Some(deduced), Ok(solved)) = (deduce(), &solved) {
+                assert_eq!(
+                    &deduced, solved,
+                    "deduction and constraint solver disagree on the wiring"
+                );
+            }
+
+            solved
+        }
     }
 }
 
-fn part2(signal_infos: &[SignalInfo]) -> u32 {
+/// Decode the output values of every entry, using the wiring determined with the given method
+fn decode_outputs(
+    signal_infos: &[SignalInfo],
+    definition: &DisplayDefinition,
+    method: SolvingMethod,
+) -> Vec<Result<String, DecodeError>> {
     // i shows as unused because of the conditional compilation on dprintln
     #[allow(unused_variables)]
     signal_infos
@@ -390,20 +729,26 @@ fn part2(signal_infos: &[SignalInfo]) -> u32 {
         .enumerate()
         .map(|(i, signal_info)| {
             dprintln!("Item {}", i + 1);
-            let segments = infer_segments(signal_info);
+            let table = solve_wiring(definition, signal_info, method)?.decoding_table(definition);
             let res = signal_info
                 .output_values
                 .iter()
-                .map(|output| {
-                    segments
-                        .decode_str(output)
-                        .unwrap_or_else(|err| panic!("Failed to decode {}: {:?}", output, err))
-                        .into()
-                })
-                .fold(0_u32, |total, n: u32| (total * 10) + n);
+                .map(|output| table.decode_str(output))
+                .collect::<Result<String, _>>()?;
 
             dprintln!("{}\n", res);
-            res
+            Ok(res)
+        })
+        .collect()
+}
+
+fn part2(decoded_outputs: &[String]) -> u32 {
+    decoded_outputs
+        .iter()
+        .map(|output| {
+            output
+                .parse::<u32>()
+                .unwrap_or_else(|err| panic!("Output {output} is not a number: {err:?}"))
         })
         .sum()
 }
@@ -421,6 +766,101 @@ fn main() {
         })
         .collect::<Vec<_>>();
 
-    println!("Part 1: {}", part1(&input_lines));
-    println!("Part 2: {}", part2(&input_lines));
+    let mut method = None;
+    let mut definition = DisplayDefinition::seven_segment();
+    let mut args = env::args().skip(2);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--constraint-solver" => method = Some(SolvingMethod::ConstraintSolver),
+            "--cross-check" => method = Some(SolvingMethod::CrossCheck),
+            "--display" => {
+                let definition_file_name =
+                    args.next().expect("No display definition file specified");
+                let raw_definition = fs::read_to_string(definition_file_name)
+                    .expect("Failed to read display definition file");
+                definition =
+                    DisplayDefinition::parse(&raw_definition).expect("Invalid display definition");
+            }
+            _ => panic!("Unknown argument '{arg}'"),
+        }
+    }
+
+    // The deduction only knows about the seven segment display, so anything else must use the constraint solver
+    let is_seven_segment = definition == DisplayDefinition::seven_segment();
+    let method = method.unwrap_or(if is_seven_segment {
+        SolvingMethod::Deduction
+    } else {
+        SolvingMethod::ConstraintSolver
+    });
+    assert!(
+        is_seven_segment || matches!(method, SolvingMethod::ConstraintSolver),
+        "Only the constraint solver supports displays other than the seven segment display"
+    );
+
+    println!("Part 1: {}", part1(&input_lines, &definition));
+
+    let decoded_outputs = decode_outputs(&input_lines, &definition, method)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, decoded)| {
+            decoded
+                .map_err(|err| eprintln!("Could not decode entry {}: {err}", i + 1))
+                .ok()
+        })
+        .collect::<Vec<_>>();
+
+    let is_partial = decoded_outputs.len() < input_lines.len();
+    if is_partial {
+        eprintln!(
+            "{} of {} entries could not be decoded, and are left out of the result",
+            input_lines.len() - decoded_outputs.len(),
+            input_lines.len()
+        );
+    }
+
+    if definition
+        .glyphs
+        .iter()
+        .all(|glyph| glyph.symbol.is_ascii_digit())
+    {
+        // A sum that's missing entries isn't the answer, so make sure it can't be mistaken for one
+        if is_partial {
+            println!(
+                "Part 2 (partial, {} of {} entries): {}",
+                decoded_outputs.len(),
+                input_lines.len(),
+                part2(&decoded_outputs)
+            );
+        } else {
+            println!("Part 2: {}", part2(&decoded_outputs));
+        }
+    } else {
+        for output in decoded_outputs {
+            println!("{output}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_ENTRY: &str =
+        "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf";
+
+    #[test]
+    fn deduction_matches_constraint_solver() {
+        let definition = DisplayDefinition::seven_segment();
+        let (_, signal_info) = parse_line(SAMPLE_ENTRY).unwrap();
+
+        let deduced = solve_wiring(&definition, &signal_info, SolvingMethod::Deduction).unwrap();
+        let solved =
+            solve_wiring(&definition, &signal_info, SolvingMethod::ConstraintSolver).unwrap();
+        assert_eq!(deduced, solved);
+
+        for method in [SolvingMethod::Deduction, SolvingMethod::ConstraintSolver] {
+            let decoded = decode_outputs(std::slice::from_ref(&signal_info), &definition, method);
+            assert_eq!(decoded[0].as_deref().unwrap(), "5353");
+        }
+    }
 }
@@ -3,64 +3,126 @@ use itertools::Itertools;
 use std::collections::{HashSet, VecDeque};
 use std::env;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader};
 use thiserror::Error;
 
+const BASIN_CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
 #[derive(Error, Debug)]
 enum Error {
     #[error("Tried to get out of bounds row, {0}")]
     RowOutOfRange(usize),
 }
 
-/// Get the indices (row, col) of all adjacent items that are in the input
-fn get_adjacent_indices(
-    input: &[Vec<u32>],
-    (depth, col): (usize, usize),
-) -> Result<Vec<(usize, usize)>, Error> {
-    let maybe_row = input.get(depth);
-    if maybe_row.is_none() {
-        return Err(Error::RowOutOfRange(depth));
-    }
+/// The label given to a single cell of the height map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Label {
+    /// A cell at or above the wall height, which separates basins
+    Ridge,
+    /// A cell that is part of the basin with the given id
+    Basin(usize),
+    /// A cell that does not flow into any low point
+    Unassigned,
+}
 
-    let row = maybe_row.unwrap();
-    let mut res = vec![];
+#[derive(Debug)]
+struct Basin {
+    low_point: (usize, usize),
+    size: u32,
+}
 
-    if col > 0 {
-        res.push((depth, col - 1));
-    }
+/// Every cell of the height map labelled with its basin, along with the basins themselves (indexed by their id)
+#[derive(Debug)]
+struct BasinMap {
+    labels: Vec<Vec<Label>>,
+    basins: Vec<Basin>,
+}
 
-    if row.get(col + 1).is_some() {
-        res.push((depth, col + 1));
+/// Which cells count as adjacent to one another
+#[derive(Debug, Clone, Copy)]
+enum Neighbourhood {
+    /// Only the cells above, below, left, and right
+    Four,
+    /// The cells above, below, left, and right, as well as the diagonals
+    Eight,
+}
+
+impl Neighbourhood {
+    /// Get the (row, col) offsets of every adjacent cell
+    fn offsets(self) -> &'static [(isize, isize)] {
+        match self {
+            Self::Four => &[(0, -1), (0, 1), (1, 0), (-1, 0)],
+            Self::Eight => &[
+                (0, -1),
+                (0, 1),
+                (1, 0),
+                (-1, 0),
+                (-1, -1),
+                (-1, 1),
+                (1, -1),
+                (1, 1),
+            ],
+        }
     }
+}
 
-    let below = input.get(depth + 1).map(|below_row| {
-        below_row
-            .get(col)
-            .expect("below row does not match row length")
-    });
-    if below.is_some() {
-        res.push((depth + 1, col));
+/// The rules for finding low points and the basins around them
+#[derive(Debug, Clone, Copy)]
+struct BasinConfig {
+    neighbourhood: Neighbourhood,
+    /// Cells at or above this height are walls, and can't be part of a basin
+    wall_height: u32,
+    /// Whether a plateau of equal cells that are lower than everything around them counts as a single low point.
+    /// Otherwise, a cell with an equal neighbour is never a low point.
+    plateau_aware: bool,
+}
+
+impl Default for BasinConfig {
+    /// The rules given by the puzzle
+    fn default() -> Self {
+        Self {
+            neighbourhood: Neighbourhood::Four,
+            wall_height: 9,
+            plateau_aware: false,
+        }
     }
+}
 
-    let have_above_item = || {
-        input
-            .get(depth - 1)
-            .map(|above_row| {
-                above_row
-                    .get(col)
-                    .expect("above row does not match row length")
-            })
-            .is_some()
-    };
-    if depth > 0 && have_above_item() {
-        res.push((depth - 1, col));
+/// Get the indices (row, col) of all adjacent items that are in the input
+fn get_adjacent_indices(
+    input: &[Vec<u32>],
+    (depth, col): (usize, usize),
+    neighbourhood: Neighbourhood,
+) -> Result<Vec<(usize, usize)>, Error> {
+    if input.get(depth).is_none() {
+        return Err(Error::RowOutOfRange(depth));
     }
 
+    let res = neighbourhood
+        .offsets()
+        .iter()
+        .filter_map(|&(row_offset, col_offset)| {
+            depth
+                .checked_add_signed(row_offset)
+                .zip(col.checked_add_signed(col_offset))
+        })
+        .filter(|&(adjacent_depth, adjacent_col)| {
+            input
+                .get(adjacent_depth)
+                .and_then(|adjacent_row| adjacent_row.get(adjacent_col))
+                .is_some()
+        })
+        .collect();
+
     Ok(res)
 }
 
 /// Get the indices of all low points in the input
-fn find_low_point_indices(input: &[Vec<u32>], depth: usize) -> Result<Vec<usize>, Error> {
+fn find_low_point_indices(
+    input: &[Vec<u32>],
+    depth: usize,
+    neighbourhood: Neighbourhood,
+) -> Result<Vec<usize>, Error> {
     let maybe_row = input.get(depth);
     if maybe_row.is_none() {
         return Err(Error::RowOutOfRange(depth));
@@ -69,7 +131,7 @@ fn find_low_point_indices(input: &[Vec<u32>], depth: usize) -> Result<Vec<usize>
     let row = maybe_row.unwrap();
     let mut res = vec![];
     for (i, &item) in row.iter().enumerate() {
-        let adjacent_res = get_adjacent_indices(input, (depth, i));
+        let adjacent_res = get_adjacent_indices(input, (depth, i), neighbourhood);
         if let Err(err) = adjacent_res {
             return Err(err);
         }
@@ -86,59 +148,217 @@ fn find_low_point_indices(input: &[Vec<u32>], depth: usize) -> Result<Vec<usize>
     Ok(res)
 }
 
-fn part1(input: &[Vec<u32>]) -> u32 {
-    input
-        .iter()
-        .enumerate()
-        .map(|(depth, row)| {
-            find_low_point_indices(input, depth)
+/// Find every plateau of equal cells (which may be a single cell) that is lower than all of the cells around it. The
+/// first cell of each plateau is the first one found scanning the input row by row.
+fn find_plateau_low_points(
+    input: &[Vec<u32>],
+    neighbourhood: Neighbourhood,
+) -> Vec<Vec<(usize, usize)>> {
+    let mut visited = HashSet::<(usize, usize)>::new();
+    let mut res = vec![];
+    for (depth, row) in input.iter().enumerate() {
+        for (col, &height) in row.iter().enumerate() {
+            if visited.contains(&(depth, col)) {
+                continue;
+            }
+
+            // Find every cell of this plateau, checking whether anything around it is lower as we go
+            let mut plateau = vec![(depth, col)];
+            let mut to_visit = [(depth, col)].into_iter().collect::<VecDeque<_>>();
+            let mut is_low_point = true;
+            visited.insert((depth, col));
+            while let Some(visiting) = to_visit.pop_front() {
+                let adjacent_iter = get_adjacent_indices(input, visiting, neighbourhood)
+                    .expect("failed to get adjacent items for plateau search");
+                for (adjacent_depth, adjacent_col) in adjacent_iter {
+                    let adjacent = input[adjacent_depth][adjacent_col];
+                    if adjacent < height {
+                        is_low_point = false;
+                    } else if adjacent == height && visited.insert((adjacent_depth, adjacent_col)) {
+                        plateau.push((adjacent_depth, adjacent_col));
+                        to_visit.push_back((adjacent_depth, adjacent_col));
+                    }
+                }
+            }
+
+            if is_low_point {
+                res.push(plateau);
+            }
+        }
+    }
+
+    res
+}
+
+/// Find every low point in the input. Each low point is made up of all of the cells of its plateau, which is only ever
+/// more than one cell if the config is plateau-aware.
+fn find_low_points(input: &[Vec<u32>], config: BasinConfig) -> Vec<Vec<(usize, usize)>> {
+    if config.plateau_aware {
+        return find_plateau_low_points(input, config.neighbourhood);
+    }
+
+    (0..input.len())
+        .flat_map(|depth| {
+            find_low_point_indices(input, depth, config.neighbourhood)
                 .expect("failed to get row for depth analysis")
                 .into_iter()
-                .map(|idx| row[idx] + 1)
-                .sum::<u32>()
+                .map(move |idx| vec![(depth, idx)])
+        })
+        .collect()
+}
+
+fn part1(input: &[Vec<u32>], config: BasinConfig) -> u32 {
+    find_low_points(input, config)
+        .into_iter()
+        .map(|cells| {
+            let (depth, col) = cells[0];
+            input[depth][col] + 1
         })
         .sum()
 }
 
-fn part2(input: &[Vec<u32>]) -> u32 {
-    let basin_sizes = input.iter().enumerate().flat_map(|(depth, _)| {
-        let low_points =
-            find_low_point_indices(input, depth).expect("failed to get row for depth analysis");
+/// Label every cell of the height map with the basin it is part of. Each basin is found by flooding outwards from
+/// its low point.
+fn label_basins(input: &[Vec<u32>], config: BasinConfig) -> BasinMap {
+    let mut labels = input
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|&height| {
+                    if height >= config.wall_height {
+                        Label::Ridge
+                    } else {
+                        Label::Unassigned
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
 
-        low_points.into_iter().map(move |low_point_idx| {
-            let mut to_visit = [(depth, low_point_idx)]
-                .into_iter()
-                .collect::<VecDeque<_>>();
-            let mut visited = HashSet::<(usize, usize)>::new();
-            // 1 includes the low point
-            let mut num_in_basin = 1;
-
-            // Flood the board, terminating our search once we hit a nine
-            while !to_visit.is_empty() {
-                let (visiting_row, visiting_col) = to_visit.pop_front().unwrap();
-                let visiting = input[visiting_row][visiting_col];
-                let adjacent_iter = get_adjacent_indices(input, (visiting_row, visiting_col))
+    let mut basins = vec![];
+    for low_point in find_low_points(input, config) {
+        let (depth, low_point_idx) = low_point[0];
+        // A low point in the walls can't hold a basin
+        if input[depth][low_point_idx] >= config.wall_height {
+            continue;
+        }
+
+        let id = basins.len();
+        for &(cell_depth, cell_col) in &low_point {
+            labels[cell_depth][cell_col] = Label::Basin(id);
+        }
+
+        // This includes every cell of the low point
+        let mut num_in_basin = u32::try_from(low_point.len()).expect("low point is too large");
+        let mut to_visit = low_point.into_iter().collect::<VecDeque<_>>();
+
+        // Flood the board, terminating our search once we hit a wall
+        while let Some((visiting_row, visiting_col)) = to_visit.pop_front() {
+            let visiting = input[visiting_row][visiting_col];
+            let adjacent_iter =
+                get_adjacent_indices(input, (visiting_row, visiting_col), config.neighbourhood)
                     .expect("failed to get adjacent items for bfs");
-                for (adjacent_row, adjacent_col) in adjacent_iter {
-                    if visited.contains(&(adjacent_row, adjacent_col)) {
-                        continue;
-                    }
+            for (adjacent_row, adjacent_col) in adjacent_iter {
+                if labels[adjacent_row][adjacent_col] != Label::Unassigned {
+                    continue;
+                }
 
-                    let adjacent = input[adjacent_row][adjacent_col];
-                    // flows from high to low, nine can't be part of the basin
-                    if adjacent > visiting && adjacent != 9 {
-                        num_in_basin += 1;
-                        to_visit.push_back((adjacent_row, adjacent_col));
-                        visited.insert((adjacent_row, adjacent_col));
-                    }
+                let adjacent = input[adjacent_row][adjacent_col];
+                // flows from high to low (or across plateaus, if we're aware of them), walls can't be part of the
+                // basin
+                let flows_here =
+                    adjacent > visiting || (config.plateau_aware && adjacent == visiting);
+                if flows_here && adjacent < config.wall_height {
+                    num_in_basin += 1;
+                    to_visit.push_back((adjacent_row, adjacent_col));
+                    labels[adjacent_row][adjacent_col] = Label::Basin(id);
                 }
             }
+        }
+
+        basins.push(Basin {
+            low_point: (depth, low_point_idx),
+            size: num_in_basin,
+        });
+    }
 
-            num_in_basin
+    BasinMap { labels, basins }
+}
+
+fn part2(basin_map: &BasinMap) -> u32 {
+    basin_map
+        .basins
+        .iter()
+        .map(|basin| basin.size)
+        .sorted()
+        .rev()
+        .take(3)
+        .product()
+}
+
+/// Get the character used to draw the basin with the given id
+fn basin_char(id: usize) -> char {
+    char::from(BASIN_CHARS[id % BASIN_CHARS.len()])
+}
+
+/// Render the basin map, drawing each basin with its own letter (which repeat if there are many basins), ridges as
+/// '#', cells that are not part of any basin as '.', and the first cell of each low point as '*'
+fn render_basin_map(basin_map: &BasinMap) -> String {
+    let low_points = basin_map
+        .basins
+        .iter()
+        .map(|basin| basin.low_point)
+        .collect::<HashSet<_>>();
+
+    basin_map
+        .labels
+        .iter()
+        .enumerate()
+        .map(|(depth, row)| {
+            row.iter()
+                .enumerate()
+                .map(|(col, label)| match label {
+                    _ if low_points.contains(&(depth, col)) => '*',
+                    Label::Ridge => '#',
+                    Label::Unassigned => '.',
+                    &Label::Basin(id) => basin_char(id),
+                })
+                .collect::<String>()
         })
-    });
+        .join("\n")
+}
 
-    basin_sizes.sorted().rev().take(3).product()
+/// Print the rendered basin map, followed by the low point and size of each basin
+fn print_basin_map(input: &[Vec<u32>], basin_map: &BasinMap) {
+    println!("{}", render_basin_map(basin_map));
+    println!("  basin  char  low point  height  size");
+    for (id, basin) in basin_map.basins.iter().enumerate() {
+        let (depth, col) = basin.low_point;
+        println!(
+            "  {:>5}  {:>4}  {:>9}  {:>6}  {:>4}",
+            id,
+            basin_char(id),
+            format!("({depth}, {col})"),
+            input[depth][col],
+            basin.size
+        );
+    }
+}
+
+/// Write the label of every cell as CSV, with one record per row of the height map. Each cell holds its basin id,
+/// "ridge", or is left empty if it is not part of any basin.
+fn write_labels<W: io::Write>(writer: W, basin_map: &BasinMap) -> Result<(), csv::Error> {
+    let mut writer = csv::Writer::from_writer(writer);
+    for row in &basin_map.labels {
+        writer.write_record(row.iter().map(|label| match label {
+            Label::Ridge => "ridge".to_string(),
+            Label::Basin(id) => id.to_string(),
+            Label::Unassigned => String::new(),
+        }))?;
+    }
+
+    writer.flush()?;
+    Ok(())
 }
 
 fn main() {
@@ -151,18 +371,50 @@ fn main() {
             s.chars()
                 .map(|c| {
                     c.to_digit(10)
-                        .unwrap_or_else(|| panic!("Expected all chars to be digits, found {}", c))
+                        .unwrap_or_else(|| panic!("Expected all chars to be digits, found {c}"))
                 })
                 .collect::<Vec<u32>>()
         })
         .collect::<Vec<_>>();
 
-    let first_row_length = input_lines.get(0).expect("input must be non-empty").len();
+    let first_row_length = input_lines.first().expect("input must be non-empty").len();
     assert!(
         input_lines.iter().all(|row| row.len() == first_row_length),
         "All input lines must be the same length"
     );
 
-    println!("Part 1: {}", part1(&input_lines));
-    println!("Part 2: {}", part2(&input_lines));
+    let mut config = BasinConfig::default();
+    let mut show_basin_map = false;
+    let mut labels_file_name = None;
+    let mut args = env::args().skip(2);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--eight-neighbours" => config.neighbourhood = Neighbourhood::Eight,
+            "--wall-height" => {
+                config.wall_height = args
+                    .next()
+                    .and_then(|n| n.parse::<u32>().ok())
+                    .expect("Wall height must be a non-negative integer");
+            }
+            "--plateaus" => config.plateau_aware = true,
+            "--basin-map" => show_basin_map = true,
+            "--labels" => {
+                labels_file_name = Some(args.next().expect("No labels output filename specified"));
+            }
+            _ => panic!("Unknown argument '{arg}'"),
+        }
+    }
+
+    println!("Part 1: {}", part1(&input_lines, config));
+    let basin_map = label_basins(&input_lines, config);
+    println!("Part 2: {}", part2(&basin_map));
+
+    if show_basin_map {
+        print_basin_map(&input_lines, &basin_map);
+    }
+
+    if let Some(labels_file_name) = labels_file_name {
+        let output_file = File::create(labels_file_name).expect("Could not create labels file");
+        write_labels(output_file, &basin_map).expect("Failed to write labels");
+    }
 }